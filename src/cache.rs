@@ -1,8 +1,47 @@
 use crate::core::error::Result;
-use bincode::Encode;
+use bincode::{Decode, Encode};
 use std::env;
-use std::fs::{self, File};
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Written at the start of every cache file so it can be told apart from the
+/// bare `Vec<Element>` layout used before headers existed
+pub const CACHE_MAGIC: [u8; 4] = *b"FRSK";
+
+/// Bump whenever `Element`, `ElementType` or the header itself changes shape,
+/// and teach `loader::decode_file` how to read the previous version.
+///
+/// 2: `Element::confirm`, plus the script filter and quicklink element types
+pub const CACHE_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct CacheHeader {
+    pub magic: [u8; 4],
+    pub version: u32,
+    pub source: String,
+    pub generated_at: u64,
+    pub count: u64,
+}
+
+impl CacheHeader {
+    pub fn new(source: &str, count: usize) -> Self {
+        let generated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            magic: CACHE_MAGIC,
+            version: CACHE_FORMAT_VERSION,
+            source: source.to_string(),
+            generated_at,
+            count: count as u64,
+        }
+    }
+}
 
 pub fn cache_dir() -> Result<PathBuf> {
     let dir = PathBuf::from(
@@ -16,15 +55,89 @@ pub fn cache_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
-pub fn save_cache<T>(name: &str, data: &T) -> Result<()>
+/// Exclusive advisory lock on a cache file, released when dropped
+pub struct CacheLock {
+    _file: File,
+}
+
+/// Take the lock for `name` around read-modify-write cycles so concurrent
+/// writers (e.g. the clipboard daemon and the picker) don't lose updates.
+/// Plain readers don't need it because `save_cache` replaces files atomically.
+#[cfg(target_os = "macos")]
+pub fn lock_cache(name: &str) -> Result<CacheLock> {
+    lock_cache_in(&cache_dir()?, name)
+}
+
+pub fn lock_cache_in(dir: &Path, name: &str) -> Result<CacheLock> {
+    let path = dir.join(format!("{}.lock", name));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)?;
+
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+
+    Ok(CacheLock { _file: file })
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+pub fn save_cache<T>(name: &str, items: &[T]) -> Result<()>
 where
     T: Encode,
 {
-    let path = cache_dir()?.join(name);
-    let mut file = File::create(&path)?;
-    let config = bincode::config::standard();
+    save_cache_in(&cache_dir()?, name, items)
+}
+
+pub fn save_cache_in<T>(dir: &Path, name: &str, items: &[T]) -> Result<()>
+where
+    T: Encode,
+{
+    let path = dir.join(name);
+    let tmp_path = dir.join(format!("{}.{}.tmp", name, std::process::id()));
+
+    if let Err(e) = write_cache_file(&tmp_path, name, items) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
 
-    bincode::encode_into_std_write(data, &mut file, config)?;
+    // Keep the previous copy around for readers to fall back on
+    if path.exists() {
+        let backup = backup_path(&path);
+        let _ = fs::remove_file(&backup);
+        if let Err(e) = fs::hard_link(&path, &backup) {
+            crate::log!("Failed to back up {}: {}", name, e);
+        }
+    }
+
+    fs::rename(&tmp_path, &path)?;
     crate::log!("Saved cache to {}", name);
     Ok(())
 }
+
+fn write_cache_file<T: Encode>(path: &Path, name: &str, items: &[T]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let config = bincode::config::standard();
+
+    let header = CacheHeader::new(name.trim_end_matches(".bin"), items.len());
+    bincode::encode_into_std_write(&header, &mut writer, config)?;
+    bincode::encode_into_std_write(items, &mut writer, config)?;
+
+    writer.flush()?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
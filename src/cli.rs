@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use crate::ipc::SearchSource;
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -17,6 +18,20 @@ pub struct Cli {
     #[arg(short, long)]
     pub prompt: Option<String>,
 
+    #[command(flatten)]
+    pub sources: SourceArgs,
+
+    /// Read items from stdin and print the selected one instead of running it
+    #[arg(long)]
+    pub dmenu: bool,
+
+    /// Print the stdin items matching a query without opening the picker
+    #[arg(short, long, requires = "dmenu")]
+    pub filter: Option<String>,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct SourceArgs {
     /// Additional binary source files to load
     #[arg(short, long)]
     pub source: Vec<PathBuf>,
@@ -44,6 +59,73 @@ pub struct Cli {
     /// Enable dictionary search (requires daemon)
     #[arg(long, alias = "dict")]
     pub dictionary: bool,
+
+    /// Load a source by name, including ones defined with [[source]] in config
+    #[arg(short, long, value_name = "SOURCE")]
+    pub with: Vec<String>,
+}
+
+impl SourceArgs {
+    /// Names of the requested sources, with the built-in flags expanded
+    pub fn names(&self) -> Vec<String> {
+        let flags = [
+            (self.apps, "apps"),
+            (self.homebrew, "homebrew"),
+            (self.clipboard, "clipboard"),
+            (self.commands, "commands"),
+            (self.nixpkgs, "nixpkgs"),
+            (self.dictionary, "dictionary"),
+        ];
+
+        flags
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| name.to_string())
+            .chain(self.with.iter().cloned())
+            .collect()
+    }
+}
+
+/// Split a command line into words, honouring quotes and backslash escapes
+#[cfg(target_os = "macos")]
+pub fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    words
 }
 
 #[derive(Subcommand, Debug)]
@@ -53,6 +135,55 @@ pub enum Commands {
         #[command(subcommand)]
         command: ServiceCommands,
     },
+    /// Print ranked results for a query without opening the picker
+    Query {
+        /// Search query
+        #[arg(default_value = "")]
+        query: String,
+
+        #[command(flatten)]
+        sources: SourceArgs,
+
+        /// Print results as a JSON array
+        #[arg(long)]
+        json: bool,
+
+        /// Maximum number of results to print
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Check or print the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Add items to the running picker until it next reloads
+    Push {
+        /// Item name, shown in the results
+        #[arg(long, required_unless_present = "stdin")]
+        name: Option<String>,
+
+        /// Defaults to the name
+        #[arg(long)]
+        value: Option<String>,
+
+        /// Element type deciding what Enter does, such as text or apps
+        #[arg(long = "type", value_name = "TYPE")]
+        element_type: Option<String>,
+
+        /// Shell command to run when the item is selected, which makes it a command
+        #[arg(long, conflicts_with_all = ["element_type", "value"])]
+        action: Option<String>,
+
+        /// Read JSON lines of name/value/type/action from stdin instead
+        #[arg(long, conflicts_with_all = ["name", "value", "element_type", "action"])]
+        stdin: bool,
+    },
+    /// Talk to the running picker
+    Ipc {
+        #[command(subcommand)]
+        command: IpcCommands,
+    },
     /// Run a daemon (called by LaunchAgent)
     Daemon {
         #[command(subcommand)]
@@ -70,6 +201,38 @@ pub enum ServiceCommands {
     List,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Report unknown keys, invalid colours and out of range values
+    Check,
+    /// Print the effective config, including defaults and built-in sources
+    Dump,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IpcCommands {
+    /// Check that a running picker is listening and answers
+    Ping,
+    /// Print ranked results from the running picker's loaded sources
+    Search {
+        /// Search query
+        #[arg(default_value = "")]
+        query: String,
+
+        /// Only search this source
+        #[arg(long, value_enum, default_value = "all")]
+        source: SearchSource,
+
+        /// Print results as a JSON array
+        #[arg(long)]
+        json: bool,
+
+        /// Maximum number of results to print
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum DaemonCommands {
     Apps,
@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::ConfigCommands;
+use crate::core::check::{self, Severity};
+use crate::core::config;
+use crate::core::error::Result;
+
+pub fn handle_config_command(cmd: ConfigCommands, config_path: Option<PathBuf>) -> Result<()> {
+    match cmd {
+        ConfigCommands::Check => {
+            let path = config::config_path(config_path)?;
+            if !check_file(&path, &mut io::stdout().lock())? {
+                std::process::exit(1);
+            }
+        }
+        ConfigCommands::Dump => {
+            print!("{}", config::dump(config_path)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Report the problems in a config file, returning false if any is an error
+fn check_file(path: &Path, out: &mut impl Write) -> Result<bool> {
+    if !path.exists() {
+        writeln!(out, "No config file at {}, using defaults", path.display())?;
+        return Ok(true);
+    }
+
+    let content = fs::read_to_string(path)?;
+    let diagnostics = check::check(&content, path);
+    for diagnostic in &diagnostics {
+        writeln!(out, "{}:{}", path.display(), diagnostic)?;
+    }
+
+    if diagnostics.is_empty() {
+        writeln!(out, "{}: no problems found", path.display())?;
+    }
+    Ok(!diagnostics.iter().any(|d| d.severity == Severity::Error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_config(name: &str, content: &str) -> (bool, String) {
+        let path = std::env::temp_dir().join(format!("frisk-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+
+        let mut out = Vec::new();
+        let passed = check_file(&path, &mut out).unwrap();
+        fs::remove_file(&path).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        (
+            passed,
+            out.replace(&path.display().to_string(), "config.toml"),
+        )
+    }
+
+    #[test]
+    fn errors_fail_the_check() {
+        let (passed, out) = check_config("errors", "font_size = 900\n");
+        assert!(!passed);
+        assert_eq!(
+            out,
+            "config.toml:1:13: error: 900 is out of range, expected 1 to 255\n"
+        );
+    }
+
+    #[test]
+    fn warnings_alone_pass() {
+        let (passed, out) = check_config("warnings", "font_sise = 20\n");
+        assert!(passed);
+        assert_eq!(out, "config.toml:1:1: warning: unknown key `font_sise`\n");
+
+        let (passed, out) = check_config("clean", "font_size = 20\n");
+        assert!(passed);
+        assert_eq!(out, "config.toml: no problems found\n");
+    }
+}
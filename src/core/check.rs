@@ -0,0 +1,407 @@
+use crate::core::color::Color;
+use crate::core::config::RawConfig;
+use crate::core::element::ElementType;
+use crate::core::keymap::{Action, Chord};
+use crate::core::theme;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::de::{DeTable, DeValue};
+use toml::Spanned;
+
+const THEME_KEYS: [&str; 2] = ["light", "dark"];
+const SPACING_KEYS: [&str; 4] = [
+    "window_padding_x",
+    "window_padding_y",
+    "prompt_to_items",
+    "item_spacing",
+];
+/// Keys of `[styles]` and `[spacing]` that are also read at the top level
+const FLAT_STYLE_KEYS: [&str; 6] = [
+    "background",
+    "items",
+    "selected_item",
+    "query",
+    "caret",
+    "match",
+];
+const FLAT_SPACING_KEYS: [&str; 3] = ["window_padding", "prompt_to_items", "item_spacing"];
+const SOURCE_KEYS: [&str; 9] = [
+    "name",
+    "path",
+    "format",
+    "enabled",
+    "priority",
+    "hint",
+    "multiplier",
+    "bias",
+    "max_results",
+];
+const RANKING_KEYS: [&str; 3] = ["multiplier", "bias", "max_results"];
+const QUICKLINK_KEYS: [&str; 3] = ["keyword", "url", "name"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem in a config file at a 1-based line and column
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, severity, self.message
+        )
+    }
+}
+
+/// Check config.toml for syntax and type errors, unknown keys, missing or
+/// broken themes, invalid colours and key bindings, and out of range values
+pub fn check(content: &str, config_path: &Path) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        content,
+        themes_dir: theme::themes_dir(config_path),
+        diagnostics: Vec::new(),
+    };
+
+    let table = match DeTable::parse(content) {
+        Ok(table) => table,
+        Err(e) => {
+            checker.push(e.span().unwrap_or(0..0), Severity::Error, e.message());
+            return checker.diagnostics;
+        }
+    };
+
+    checker.check_config(table.get_ref());
+
+    // Wrong types are left to serde, unless already reported at that spot
+    if let Err(e) = toml::from_str::<RawConfig>(content) {
+        let span = e.span().unwrap_or(0..0);
+        let (line, _) = checker.position(span.start);
+        if !checker.diagnostics.iter().any(|d| d.line == line) {
+            checker.push(span, Severity::Error, e.message());
+        }
+    }
+
+    checker
+        .diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    content: &'a str,
+    themes_dir: PathBuf,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn check_config(&mut self, table: &DeTable) {
+        let known = RawConfig::keys();
+
+        for (key, value) in table.iter() {
+            let name = key.get_ref().as_ref();
+            match name {
+                _ if FLAT_STYLE_KEYS.contains(&name) => self.check_color(value),
+                _ if FLAT_SPACING_KEYS.contains(&name) => self.check_min(value, 0.0),
+                "font_size" => self.check_range(value, 1.0, 255.0),
+                "window_opacity" => self.check_range(value, 0.0, 1.0),
+                "theme" => {
+                    self.check_theme(value);
+                    self.check_keys(value, &THEME_KEYS);
+                    self.check_values(value, |checker, key, value| {
+                        if THEME_KEYS.contains(&key) {
+                            checker.check_theme(value);
+                        }
+                    });
+                }
+                "styles" => {
+                    self.check_keys(value, &theme::STYLE_KEYS);
+                    self.check_values(value, |checker, _, value| checker.check_color(value));
+                }
+                "spacing" => {
+                    self.check_keys(value, &SPACING_KEYS);
+                    self.check_values(value, |checker, _, value| checker.check_min(value, 0.0));
+                }
+                "prefixes" => self.check_type_keys(value, "prefix scope"),
+                "ranking" => {
+                    self.check_type_keys(value, "ranking type");
+                    if let DeValue::Table(ranking) = value.get_ref() {
+                        for value in ranking.values() {
+                            self.check_ranked_table(value, &RANKING_KEYS);
+                        }
+                    }
+                }
+                "source" => self.check_tables(value, |checker, value| {
+                    checker.check_ranked_table(value, &SOURCE_KEYS)
+                }),
+                "quicklink" => self.check_tables(value, |checker, value| {
+                    checker.check_keys(value, &QUICKLINK_KEYS)
+                }),
+                "keys" => self.check_bindings(value),
+                _ if known.iter().any(|k| k == name) => {}
+                _ => self.push(
+                    key.span(),
+                    Severity::Warning,
+                    format!("unknown key `{}`", name),
+                ),
+            }
+        }
+    }
+
+    fn check_color(&mut self, value: &Spanned<DeValue>) {
+        let DeValue::String(color) = value.get_ref() else {
+            return;
+        };
+        if let Err(e) = Color::parse(color) {
+            self.push(value.span(), Severity::Error, e.to_string());
+        }
+    }
+
+    fn check_theme(&mut self, value: &Spanned<DeValue>) {
+        let DeValue::String(name) = value.get_ref() else {
+            return;
+        };
+        if !theme::exists(&self.themes_dir, name) {
+            self.push(
+                value.span(),
+                Severity::Error,
+                format!(
+                    "unknown theme `{}`, not built in or in {}",
+                    name,
+                    self.themes_dir.display()
+                ),
+            );
+        } else if let Err(e) = theme::load(&self.themes_dir, name) {
+            self.push(value.span(), Severity::Error, e.to_string());
+        }
+    }
+
+    fn check_bindings(&mut self, value: &Spanned<DeValue>) {
+        let DeValue::Table(table) = value.get_ref() else {
+            return;
+        };
+        for (chord, action) in table.iter() {
+            if let Err(e) = Chord::parse(chord.get_ref()) {
+                self.push(chord.span(), Severity::Error, e.to_string());
+            }
+            if let DeValue::String(name) = action.get_ref() {
+                if let Err(e) = Action::parse(name) {
+                    self.push(action.span(), Severity::Error, e.to_string());
+                }
+            }
+        }
+    }
+
+    fn check_range(&mut self, value: &Spanned<DeValue>, min: f64, max: f64) {
+        let Some(number) = number(value.get_ref()) else {
+            return;
+        };
+        if number < min || number > max {
+            self.push(
+                value.span(),
+                Severity::Error,
+                format!("{} is out of range, expected {} to {}", number, min, max),
+            );
+        }
+    }
+
+    fn check_min(&mut self, value: &Spanned<DeValue>, min: f64) {
+        let Some(number) = number(value.get_ref()) else {
+            return;
+        };
+        if number < min {
+            self.push(
+                value.span(),
+                Severity::Error,
+                format!("{} is out of range, expected at least {}", number, min),
+            );
+        }
+    }
+
+    /// Keys of a table that name element types
+    fn check_type_keys(&mut self, value: &Spanned<DeValue>, what: &str) {
+        let DeValue::Table(table) = value.get_ref() else {
+            return;
+        };
+        for key in table.keys() {
+            if ElementType::from_name(key.get_ref()).is_none() {
+                self.push(
+                    key.span(),
+                    Severity::Error,
+                    format!("unknown {} `{}`", what, key.get_ref()),
+                );
+            }
+        }
+    }
+
+    fn check_tables(
+        &mut self,
+        value: &Spanned<DeValue>,
+        check: impl Fn(&mut Self, &Spanned<DeValue>),
+    ) {
+        if let DeValue::Array(items) = value.get_ref() {
+            for item in items.iter() {
+                check(self, item);
+            }
+        }
+    }
+
+    fn check_values(
+        &mut self,
+        value: &Spanned<DeValue>,
+        check: impl Fn(&mut Self, &str, &Spanned<DeValue>),
+    ) {
+        if let DeValue::Table(table) = value.get_ref() {
+            for (key, value) in table.iter() {
+                check(self, key.get_ref(), value);
+            }
+        }
+    }
+
+    fn check_ranked_table(&mut self, value: &Spanned<DeValue>, keys: &[&str]) {
+        self.check_keys(value, keys);
+        self.check_values(value, |checker, key, value| match key {
+            "multiplier" => checker.check_min(value, 0.0),
+            "max_results" => checker.check_min(value, 1.0),
+            _ => {}
+        });
+    }
+
+    fn check_keys(&mut self, value: &Spanned<DeValue>, keys: &[&str]) {
+        let DeValue::Table(table) = value.get_ref() else {
+            return;
+        };
+        for key in table.keys() {
+            if !keys.contains(&key.get_ref().as_ref()) {
+                self.push(
+                    key.span(),
+                    Severity::Warning,
+                    format!("unknown key `{}`", key.get_ref()),
+                );
+            }
+        }
+    }
+
+    fn push(&mut self, span: Range<usize>, severity: Severity, message: impl Into<String>) {
+        let (line, column) = self.position(span.start);
+        self.diagnostics.push(Diagnostic {
+            line,
+            column,
+            severity,
+            message: message.into(),
+        });
+    }
+
+    /// Line and column of a byte offset, both starting at 1
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content[..offset.min(self.content.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+fn number(value: &DeValue) -> Option<f64> {
+    match value {
+        DeValue::Integer(integer) => i64::from_str_radix(integer.as_str(), integer.radix())
+            .ok()
+            .map(|n| n as f64),
+        DeValue::Float(float) => float.as_str().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostics(content: &str) -> Vec<String> {
+        check(content, Path::new("/nonexistent/frisk/config.toml"))
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn unknown_keys_are_warnings() {
+        assert_eq!(
+            diagnostics("font_size = 20\nfont_sise = 20\n\n[styles]\n  itmes = \"#fff\"\n"),
+            [
+                "2:1: warning: unknown key `font_sise`",
+                "5:3: warning: unknown key `itmes`",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_colours_point_at_the_value() {
+        let diagnostics = diagnostics("[styles]\nitems = \"#ggg\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(
+            diagnostics[0].starts_with("2:9: error: invalid colour `#ggg`"),
+            "{}",
+            diagnostics[0]
+        );
+    }
+
+    #[test]
+    fn flat_keys_are_checked_next_to_nested_ones() {
+        let content = "\
+background = \"#000\"
+window_padding = 10
+match = \"blue\"
+
+[styles]
+items = \"#fff\"
+
+[spacing]
+item_spacing = 4
+";
+        assert_eq!(diagnostics(content), Vec::<String>::new());
+
+        let content = "caret = \"nope\"\n\n[spacing]\nitem_spacing = -1\n";
+        let diagnostics = diagnostics(content);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].starts_with("1:9: error: invalid colour `nope`"));
+        assert_eq!(
+            diagnostics[1],
+            "4:16: error: -1 is out of range, expected at least 0"
+        );
+    }
+
+    #[test]
+    fn theme_files_are_checked() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let themes_dir = theme::themes_dir(&config_path);
+        std::fs::create_dir(&themes_dir).unwrap();
+        std::fs::write(theme::theme_path(&themes_dir, "mine"), "itmes = \"#fff\"\n").unwrap();
+
+        let diagnostics: Vec<String> = check(
+            "theme = { light = \"mine\", dark = \"nord\" }\n",
+            &config_path,
+        )
+        .iter()
+        .map(Diagnostic::to_string)
+        .collect();
+        assert_eq!(
+            diagnostics,
+            ["1:19: error: Theme mine: unknown key `itmes`"]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use crate::core::error::{Error, Result};
+
+/// An sRGB colour with alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Parse `#RGB`, `#RGBA`, `#RRGGBB`, `#RRGGBBAA`, `rgb()`, `rgba()`,
+    /// `hsl()`, `hsla()` or a CSS colour name
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let invalid = |reason: &str| Error::new(format!("invalid colour `{}`: {}", value, reason));
+
+        if let Some(color) = named(value) {
+            return Ok(color);
+        }
+
+        if let Some((function, args)) = value
+            .strip_suffix(')')
+            .and_then(|value| value.split_once('('))
+        {
+            let args = arguments(args);
+            return match function.trim().to_ascii_lowercase().as_str() {
+                "rgb" | "rgba" => rgb_function(&args),
+                "hsl" | "hsla" => hsl_function(&args),
+                _ => Err("expected rgb(), rgba(), hsl() or hsla()".into()),
+            }
+            .map_err(|reason: String| invalid(&reason));
+        }
+
+        hex(value.strip_prefix('#').unwrap_or(value)).ok_or_else(|| {
+            invalid("expected #RGB, #RGBA, #RRGGBB, #RRGGBBAA, a colour function or a name")
+        })
+    }
+}
+
+/// Channels as the fractions AppKit takes
+#[cfg(any(target_os = "macos", test))]
+impl Color {
+    pub fn red(&self) -> f64 {
+        self.r as f64 / 255.0
+    }
+
+    pub fn green(&self) -> f64 {
+        self.g as f64 / 255.0
+    }
+
+    pub fn blue(&self) -> f64 {
+        self.b as f64 / 255.0
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.a as f64 / 255.0
+    }
+}
+
+fn hex(digits: &str) -> Option<Color> {
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize, width: usize| {
+        let digits = &digits[i * width..(i + 1) * width];
+        let value = u8::from_str_radix(digits, 16).ok()?;
+        Some(if width == 1 { value * 17 } else { value })
+    };
+
+    match digits.len() {
+        3 => Some(Color::rgb(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+        4 => Some(Color::rgba(
+            channel(0, 1)?,
+            channel(1, 1)?,
+            channel(2, 1)?,
+            channel(3, 1)?,
+        )),
+        6 => Some(Color::rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+        8 => Some(Color::rgba(
+            channel(0, 2)?,
+            channel(1, 2)?,
+            channel(2, 2)?,
+            channel(3, 2)?,
+        )),
+        _ => None,
+    }
+}
+
+/// Split function arguments given either as `a, b, c, d` or `a b c / d`
+fn arguments(args: &str) -> Vec<&str> {
+    args.split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect()
+}
+
+fn rgb_function(args: &[&str]) -> std::result::Result<Color, String> {
+    let (r, g, b, a) = match args {
+        [r, g, b] => (r, g, b, None),
+        [r, g, b, a] => (r, g, b, Some(a)),
+        _ => return Err("expected 3 or 4 arguments".into()),
+    };
+
+    let channel = |arg: &str| match arg.strip_suffix('%') {
+        Some(percent) => number(percent, 0.0, 100.0).map(|p| p / 100.0 * 255.0),
+        None => number(arg, 0.0, 255.0),
+    };
+
+    Ok(Color::rgba(
+        channel(r)?.round() as u8,
+        channel(g)?.round() as u8,
+        channel(b)?.round() as u8,
+        a.map_or(Ok(255), |a| alpha(a))?,
+    ))
+}
+
+fn hsl_function(args: &[&str]) -> std::result::Result<Color, String> {
+    let (h, s, l, a) = match args {
+        [h, s, l] => (h, s, l, None),
+        [h, s, l, a] => (h, s, l, Some(a)),
+        _ => return Err("expected 3 or 4 arguments".into()),
+    };
+
+    let hue = h.strip_suffix("deg").unwrap_or(h);
+    let hue = hue
+        .parse::<f64>()
+        .map_err(|_| format!("`{}` is not a number", h))?
+        .rem_euclid(360.0);
+    let percent = |arg: &str| {
+        let value = arg
+            .strip_suffix('%')
+            .ok_or_else(|| format!("expected a percentage, got `{}`", arg))?;
+        number(value, 0.0, 100.0).map(|p| p / 100.0)
+    };
+    let (s, l) = (percent(s)?, percent(l)?);
+
+    // https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+    let channel = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        let value = l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+        (value * 255.0).round() as u8
+    };
+
+    Ok(Color::rgba(
+        channel(0.0),
+        channel(8.0),
+        channel(4.0),
+        a.map_or(Ok(255), |a| alpha(a))?,
+    ))
+}
+
+/// An alpha of 0 to 1 or a percentage
+fn alpha(arg: &str) -> std::result::Result<u8, String> {
+    let alpha = match arg.strip_suffix('%') {
+        Some(percent) => number(percent, 0.0, 100.0)? / 100.0,
+        None => number(arg, 0.0, 1.0)?,
+    };
+    Ok((alpha * 255.0).round() as u8)
+}
+
+fn number(arg: &str, min: f64, max: f64) -> std::result::Result<f64, String> {
+    let value: f64 = arg
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", arg))?;
+    if !(min..=max).contains(&value) {
+        return Err(format!(
+            "{} is out of range, expected {} to {}",
+            arg, min, max
+        ));
+    }
+    Ok(value)
+}
+
+fn named(name: &str) -> Option<Color> {
+    if name.eq_ignore_ascii_case("transparent") {
+        return Some(Color::rgba(0, 0, 0, 0));
+    }
+
+    let rgb = NAMED_COLORS
+        .iter()
+        .find(|(named, _)| named.eq_ignore_ascii_case(name))
+        .map(|(_, rgb)| *rgb)?;
+    Some(Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+/// CSS named colours, https://www.w3.org/TR/css-color-4/#named-colors
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Color {
+        Color::parse(value).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert_eq!(parse("#fff"), Color::rgb(255, 255, 255));
+        assert_eq!(parse("#f00a"), Color::rgba(255, 0, 0, 170));
+        assert_eq!(parse("#282c34"), Color::rgb(40, 44, 52));
+        assert_eq!(parse("#282c34f0"), Color::rgba(40, 44, 52, 240));
+        assert_eq!(parse("#61AFEF"), Color::rgb(97, 175, 239));
+        // Configs written before the `#` was documented
+        assert_eq!(parse("e06c75"), Color::rgb(224, 108, 117));
+    }
+
+    #[test]
+    fn rgb_functions() {
+        assert_eq!(parse("rgb(97, 175, 239)"), Color::rgb(97, 175, 239));
+        assert_eq!(parse("rgba(40,44,52,0.5)"), Color::rgba(40, 44, 52, 128));
+        assert_eq!(parse("rgb(40 44 52 / 50%)"), Color::rgba(40, 44, 52, 128));
+        assert_eq!(parse("rgb(100%, 0%, 50%)"), Color::rgb(255, 0, 128));
+        assert_eq!(parse("RGB(0, 0, 0)"), Color::rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn hsl_functions() {
+        assert_eq!(parse("hsl(0, 100%, 50%)"), Color::rgb(255, 0, 0));
+        assert_eq!(parse("hsl(120deg 100% 25%)"), Color::rgb(0, 128, 0));
+        assert_eq!(parse("hsl(240, 100%, 50%)"), Color::rgb(0, 0, 255));
+        assert_eq!(parse("hsl(-120, 100%, 50%)"), Color::rgb(0, 0, 255));
+        assert_eq!(
+            parse("hsla(0, 0%, 100%, 0.25)"),
+            Color::rgba(255, 255, 255, 64)
+        );
+        assert_eq!(parse("hsl(207, 82%, 66%)"), Color::rgb(97, 175, 239));
+    }
+
+    #[test]
+    fn named_colours() {
+        assert_eq!(parse("rebeccapurple"), Color::rgb(102, 51, 153));
+        assert_eq!(parse("White"), Color::rgb(255, 255, 255));
+        assert_eq!(parse("transparent"), Color::rgba(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn invalid_colours() {
+        for value in [
+            "",
+            "#",
+            "#12",
+            "#12345",
+            "#1234567",
+            "#ggg",
+            "notacolour",
+            "rgb(1, 2)",
+            "rgb(256, 0, 0)",
+            "rgba(0, 0, 0, 2)",
+            "rgb(a, b, c)",
+            "hsl(0, 50, 50)",
+            "hsl(0, 150%, 50%)",
+            "lab(50% 40 59)",
+        ] {
+            assert!(Color::parse(value).is_err(), "{} should be invalid", value);
+        }
+    }
+
+    #[test]
+    fn channels() {
+        let color = parse("#ff000080");
+        assert_eq!(color.red(), 1.0);
+        assert_eq!(color.green(), 0.0);
+        assert_eq!(color.blue(), 0.0);
+        assert!((color.alpha() - 0.502).abs() < 0.001);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::core::element::Element;
+#[cfg(any(target_os = "macos", test))]
+use crate::core::error::Error;
 use crate::core::error::Result;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize)]
 pub struct CommandsConfig {
@@ -15,6 +18,33 @@ pub struct CommandsConfig {
 pub struct CustomCommand {
     pub name: String,
     pub action: String,
+    /// Show the action's JSON lines output as a new result list instead of
+    /// running it in the background
+    #[serde(default)]
+    pub filter: bool,
+    /// Ask for confirmation in the picker before running
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(flatten)]
+    pub options: CommandOptions,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct CommandOptions {
+    /// Working directory, `~` is expanded
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Shell that runs the action with `-c`, `sh` by default
+    pub shell: Option<String>,
+    /// Open the action in the terminal from config.toml
+    #[serde(default)]
+    pub run_in_terminal: bool,
+    /// Keep the picker open until the action exits and report failures
+    #[serde(default)]
+    pub wait: bool,
+    /// Seconds to wait before killing the action, implies `wait`
+    pub timeout: Option<u64>,
 }
 
 impl CommandsConfig {
@@ -30,12 +60,26 @@ impl CommandsConfig {
             eprintln!("Created default commands config at: {}", path.display());
         }
 
-        let content = fs::read_to_string(&path)?;
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
         let config: CommandsConfig = toml::from_str(&content)?;
         Ok(config)
     }
 
-    fn config_path() -> Result<PathBuf> {
+    /// Check commands files that changed on disk, skipping removed ones, as
+    /// the loader only warns about them
+    #[cfg(any(target_os = "macos", test))]
+    pub fn check_files(paths: &[PathBuf]) -> Result<()> {
+        for path in paths.iter().filter(|path| path.exists()) {
+            Self::load_from(path).map_err(|e| Error::new(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn config_path() -> Result<PathBuf> {
         let mut path = if let Ok(home) = env::var("HOME") {
             PathBuf::from(home)
         } else {
@@ -62,10 +106,12 @@ action = "osascript -e 'tell application \"Finder\" to open trash' && open -a fi
 [[command]]
 name = "Restart"
 action = "osascript -e 'tell application \"System Events\" to restart'"
+confirm = true
 
 [[command]]
 name = "Shut Down"
 action = "osascript -e 'tell application \"System Events\" to shut down'"
+confirm = true
 
 [[command]]
 name = "Sleep"
@@ -81,7 +127,43 @@ action = "pmset displaysleepnow"
     pub fn to_elements(&self) -> Vec<Element> {
         self.command
             .iter()
-            .map(|cmd| Element::new_system_command(cmd.name.clone(), cmd.action.clone()))
+            .map(|cmd| {
+                let mut element = if cmd.filter {
+                    Element::new_script_filter(cmd.name.clone(), cmd.action.clone())
+                } else {
+                    Element::new_system_command(cmd.name.clone(), cmd.action.clone())
+                };
+                element.confirm = cmd.confirm;
+                element
+            })
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn changed_files_are_checked_unless_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let good = dir.path().join("good.toml");
+        let bad = dir.path().join("bad.toml");
+        fs::write(
+            &good,
+            "[[command]]\nname = \"Sleep\"\naction = \"pmset sleepnow\"\n",
+        )
+        .unwrap();
+        fs::write(&bad, "[[command]]\nname = \"Sleep\"\n").unwrap();
+
+        let removed = dir.path().join("removed.toml");
+        assert!(CommandsConfig::check_files(&[good.clone(), removed]).is_ok());
+        let error = CommandsConfig::check_files(&[good, bad.clone()])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with(&format!("{}: ", bad.display())),
+            "{}",
+            error
+        );
+    }
+}
@@ -1,25 +1,84 @@
+use crate::core::check;
+#[cfg(target_os = "macos")]
+use crate::core::color::Color;
+#[cfg(target_os = "macos")]
+use crate::core::commands::CommandsConfig;
+#[cfg(any(target_os = "macos", test))]
+use crate::core::element::{ElementList, ElementType, Scope};
 use crate::core::error::{Error, Result};
+use crate::core::keymap::Keymap;
+use crate::core::quicklink::Quicklink;
+#[cfg(target_os = "macos")]
+use crate::core::source::SourceFormat;
+use crate::core::source::{Ranking, Source, SourceConfig, SourceRegistry};
+use crate::core::theme::{self, Styles, ThemeSetting};
+#[cfg(target_os = "macos")]
+use crate::loader::expand_home;
+#[cfg(target_os = "macos")]
 use objc2::rc::Retained;
+#[cfg(target_os = "macos")]
 use objc2_app_kit::{NSColor, NSFont};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
-struct RawConfig {
+pub(crate) struct RawConfig {
     prompt: String,
     font_family: String,
-    font_size: u8,
+    font_size: f32,
     window_opacity: f32,
-    window_padding: u8,
-    prompt_to_items: u8,
-    item_spacing: u8,
-    background: String,
-    items: String,
-    selected_item: String,
-    query: String,
-    caret: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theme: Option<ThemeSetting>,
+    styles: Styles,
+    spacing: Spacing,
+    /// Command template for `run_in_terminal` commands, `{command}` is replaced
+    /// by the command line. Terminal.app is used when empty.
+    terminal: String,
+    prefixes: BTreeMap<String, String>,
+    source: Vec<SourceConfig>,
+    ranking: BTreeMap<String, Ranking>,
+    quicklink: Vec<Quicklink>,
+    /// Chords bound to actions on top of the default bindings
+    keys: BTreeMap<String, String>,
+
+    // Flat keys from before `[styles]` and `[spacing]`, still read and moved
+    // into those tables
+    #[serde(skip_serializing)]
+    window_padding: Option<f32>,
+    #[serde(skip_serializing)]
+    prompt_to_items: Option<f32>,
+    #[serde(skip_serializing)]
+    item_spacing: Option<f32>,
+    #[serde(skip_serializing)]
+    background: Option<String>,
+    #[serde(skip_serializing)]
+    items: Option<String>,
+    #[serde(skip_serializing)]
+    selected_item: Option<String>,
+    #[serde(skip_serializing)]
+    query: Option<String>,
+    #[serde(skip_serializing)]
+    caret: Option<String>,
+    #[serde(rename = "match", skip_serializing)]
+    match_text: Option<String>,
+}
+
+/// Gaps around and between the prompt and items, unset ones are taken from
+/// the flat keys and then the defaults
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Spacing {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_padding_x: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_padding_y: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_to_items: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item_spacing: Option<f32>,
 }
 
 impl Default for RawConfig {
@@ -27,157 +86,468 @@ impl Default for RawConfig {
         Self {
             prompt: "Run: ".into(),
             font_family: "Berkeley Mono".into(),
-            font_size: 32,
+            font_size: 32.0,
             window_opacity: 0.55,
-            window_padding: 20,
-            prompt_to_items: 60,
-            item_spacing: 15,
-            background: "#282c34".into(),
-            items: "#ffffff".into(),
-            selected_item: "#61afef".into(),
-            query: "#e06c75".into(),
-            caret: "#e06c75".into(),
+            theme: None,
+            styles: Styles::default(),
+            spacing: Spacing::default(),
+            terminal: String::new(),
+            prefixes: BTreeMap::from([
+                ("homebrew".into(), "b ".into()),
+                ("nixpkgs".into(), "n ".into()),
+                ("commands".into(), "> ".into()),
+                ("calculator".into(), "= ".into()),
+            ]),
+            source: Vec::new(),
+            ranking: BTreeMap::new(),
+            quicklink: Vec::new(),
+            keys: BTreeMap::new(),
+            window_padding: None,
+            prompt_to_items: None,
+            item_spacing: None,
+            background: None,
+            items: None,
+            selected_item: None,
+            query: None,
+            caret: None,
+            match_text: None,
+        }
+    }
+}
+
+impl Spacing {
+    fn defaults() -> Self {
+        Self {
+            window_padding_x: Some(20.0),
+            window_padding_y: Some(20.0),
+            prompt_to_items: Some(60.0),
+            item_spacing: Some(15.0),
+        }
+    }
+
+    /// This spacing, with unset gaps taken from `below`
+    fn or(self, below: Spacing) -> Self {
+        Self {
+            window_padding_x: self.window_padding_x.or(below.window_padding_x),
+            window_padding_y: self.window_padding_y.or(below.window_padding_y),
+            prompt_to_items: self.prompt_to_items.or(below.prompt_to_items),
+            item_spacing: self.item_spacing.or(below.item_spacing),
         }
     }
 }
 
+#[cfg(target_os = "macos")]
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// The file this config was read from, which may not exist
+    pub path: PathBuf,
     pub prompt: String,
-    pub font_size: u8,
-    pub window_padding: u8,
-    pub prompt_to_items: u8,
-    pub item_spacing: u8,
+    /// A prompt from the command line or a reload request, used over the
+    /// configured one until the picker exits
+    pub prompt_override: Option<String>,
+    pub font_size: f64,
+    pub window_padding_x: f64,
+    pub window_padding_y: f64,
+    pub prompt_to_items: f64,
+    pub item_spacing: f64,
     pub font: Retained<NSFont>,
     pub background_color: Retained<NSColor>,
     pub items_color: Retained<NSColor>,
     pub selected_item_color: Retained<NSColor>,
+    pub prompt_color: Retained<NSColor>,
     pub query_color: Retained<NSColor>,
     pub caret_color: Retained<NSColor>,
+    pub match_color: Retained<NSColor>,
+    /// Query prefixes restricting the search to a scope, longest first
+    pub prefixes: Vec<(String, Scope)>,
+    pub sources: SourceRegistry,
+    pub terminal: String,
+    /// Keyword searches, longest keyword first
+    pub quicklinks: Vec<Quicklink>,
+    pub keymap: Keymap,
+    /// Files the theme may be read from
+    pub theme_files: Vec<PathBuf>,
 }
 
-impl Config {
-    pub fn load(config_path: Option<PathBuf>) -> Result<Self> {
-        let path = match config_path {
-            Some(path) => path,
-            None => PathBuf::from(
-                std::env::var("XDG_CONFIG_HOME")
-                    .or_else(|_| std::env::var("HOME").map(|home| format!("{}/.cache", home)))
-                    .map_err(|_| {
-                        Error::new("Neither $XDG_CONFIG_HOME or $HOME variables are set.")
-                    })?,
-            )
-            .join("frisk")
-            .join("config.toml"),
-        };
+impl RawConfig {
+    /// Every top-level key, taken from the serialized defaults
+    pub(crate) fn keys() -> Vec<String> {
+        toml::Table::try_from(Self::default())
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default()
+    }
 
-        let raw = if path.exists() {
-            let content = fs::read_to_string(&path)?;
+    fn load(path: &Path) -> Result<Self> {
+        let mut raw = if path.exists() {
+            let content = fs::read_to_string(path)?;
+            if cfg!(debug_assertions) {
+                for diagnostic in check::check(&content, path) {
+                    crate::log!("{}:{}", path.display(), diagnostic);
+                }
+            }
             toml::from_str(&content)?
         } else {
-            RawConfig::default()
+            Self::default()
         };
 
-        Self::from_raw(raw)
+        raw.move_flat_keys();
+        raw.apply_theme(path)?;
+        Ok(raw)
     }
 
-    fn from_raw(raw: RawConfig) -> Result<Self> {
-        let font = Self::create_font(&raw.font_family, raw.font_size);
-        let background_color =
-            Self::parse_color_with_alpha(&raw.background, 40, 44, 52, raw.window_opacity)?;
-        let items_color = Self::parse_color(&raw.items, 255, 255, 255)?;
-        let selected_item_color = Self::parse_color(&raw.selected_item, 97, 175, 239)?;
-        let query_color = Self::parse_color(&raw.query, 224, 108, 117)?;
-        let caret_color = Self::parse_color(&raw.caret, 224, 108, 117)?;
+    /// Flat keys from configs written before `[styles]` and `[spacing]`
+    /// existed fill what the tables leave unset
+    fn move_flat_keys(&mut self) {
+        let padding = self.window_padding.take();
+        let flat = Spacing {
+            window_padding_x: padding,
+            window_padding_y: padding,
+            prompt_to_items: self.prompt_to_items.take(),
+            item_spacing: self.item_spacing.take(),
+        };
+        self.spacing = std::mem::take(&mut self.spacing)
+            .or(flat)
+            .or(Spacing::defaults());
+
+        let flat = Styles {
+            background: self.background.take(),
+            items: self.items.take(),
+            selected_item: self.selected_item.take(),
+            prompt: None,
+            query: self.query.take(),
+            caret: self.caret.take(),
+            match_text: self.match_text.take(),
+        };
+        self.styles = std::mem::take(&mut self.styles).or(flat);
+    }
+
+    /// Fill the colours `[styles]` doesn't set from the theme, then the defaults
+    fn apply_theme(&mut self, config_path: &Path) -> Result<()> {
+        let theme = match &self.theme {
+            Some(theme) => theme::load(&theme::themes_dir(config_path), theme.name())?,
+            None => Styles::default(),
+        };
+        self.styles = std::mem::take(&mut self.styles)
+            .or(theme)
+            .or(Styles::defaults());
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Config {
+    pub fn load(config_path: Option<PathBuf>) -> Result<Self> {
+        let path = self::config_path(config_path)?;
+        let raw = RawConfig::load(&path)?;
+        Self::from_raw(path, raw)
+    }
+
+    fn from_raw(path: PathBuf, raw: RawConfig) -> Result<Self> {
+        let styles = &raw.styles;
+        let font = Self::create_font(&raw.font_family, raw.font_size as f64);
+        // An opaque background takes its alpha from window_opacity
+        let background = Self::parse_style("background", styles.background.as_deref())?;
+        let background_alpha = match background.a {
+            255 => raw.window_opacity as f64,
+            _ => background.alpha(),
+        };
+        let background_color = Self::ns_color(background, background_alpha);
+        let items_color = Self::color("items", styles.items.as_deref())?;
+        let selected_item_color = Self::color("selected_item", styles.selected_item.as_deref())?;
+        let query_color = Self::color("query", styles.query.as_deref())?;
+        let prompt_color = match &styles.prompt {
+            Some(prompt) => Self::color("prompt", Some(prompt))?,
+            None => query_color.clone(),
+        };
+        let caret_color = Self::color("caret", styles.caret.as_deref())?;
+        let match_color = Self::color("match", styles.match_text.as_deref())?;
+        let theme_files = match &raw.theme {
+            Some(theme) => {
+                let themes_dir = theme::themes_dir(&path);
+                theme
+                    .names()
+                    .into_iter()
+                    .map(|name| theme::theme_path(&themes_dir, name))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let prefixes = parse_prefixes(&raw.prefixes)?;
+        let sources = SourceRegistry::new(raw.source, &raw.ranking)?;
+        let mut quicklinks = raw.quicklink;
+        quicklinks.sort_by_key(|quicklink| std::cmp::Reverse(quicklink.keyword.len()));
+        let keymap = parse_keys(&raw.keys)?;
 
         Ok(Self {
+            path,
             prompt: raw.prompt,
-            font_size: raw.font_size,
-            window_padding: raw.window_padding,
-            prompt_to_items: raw.prompt_to_items,
-            item_spacing: raw.item_spacing,
+            prompt_override: None,
+            font_size: raw.font_size as f64,
+            // Every gap is set once the flat keys are moved
+            window_padding_x: raw.spacing.window_padding_x.unwrap_or_default() as f64,
+            window_padding_y: raw.spacing.window_padding_y.unwrap_or_default() as f64,
+            prompt_to_items: raw.spacing.prompt_to_items.unwrap_or_default() as f64,
+            item_spacing: raw.spacing.item_spacing.unwrap_or_default() as f64,
             font,
             background_color,
             items_color,
             selected_item_color,
+            prompt_color,
             query_color,
             caret_color,
+            match_color,
+            prefixes,
+            sources,
+            terminal: raw.terminal,
+            quicklinks,
+            keymap,
+            theme_files,
         })
     }
 
-    fn create_font(font_family: &str, font_size: u8) -> Retained<NSFont> {
+    /// Use `prompt` over the configured one, including after a reload
+    pub fn override_prompt(&mut self, prompt: String) {
+        self.prompt = prompt.clone();
+        self.prompt_override = Some(prompt);
+    }
+
+    /// config.toml, its theme files and the commands files its sources read
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.path.clone()];
+        files.extend(self.theme_files.iter().cloned());
+
+        for source in self.sources.all() {
+            if source.format != SourceFormat::Commands {
+                continue;
+            }
+            match &source.path {
+                Some(path) => files.push(expand_home(path)),
+                None => files.extend(CommandsConfig::config_path().ok()),
+            }
+        }
+
+        files
+    }
+
+    fn create_font(font_family: &str, font_size: f64) -> Retained<NSFont> {
         if font_family.is_empty() || font_family == "system" {
-            return NSFont::systemFontOfSize(font_size as f64);
+            return NSFont::systemFontOfSize(font_size);
         }
 
         use objc2_foundation::NSString;
         let font_name_ns = NSString::from_str(font_family);
-        NSFont::fontWithName_size(&font_name_ns, font_size as f64)
-            .unwrap_or_else(|| NSFont::systemFontOfSize(font_size as f64))
-    }
-
-    fn parse_color(
-        hex: &str,
-        fallback_r: u8,
-        fallback_g: u8,
-        fallback_b: u8,
-    ) -> Result<Retained<NSColor>> {
-        let hex = hex.trim_start_matches('#');
-
-        let (r, g, b) = match hex.len() {
-            3 => {
-                let r = u8::from_str_radix(&hex[0..1].repeat(2), 16)?;
-                let g = u8::from_str_radix(&hex[1..2].repeat(2), 16)?;
-                let b = u8::from_str_radix(&hex[2..3].repeat(2), 16)?;
-                (r, g, b)
-            }
-            6 => {
-                let r = u8::from_str_radix(&hex[0..2], 16)?;
-                let g = u8::from_str_radix(&hex[2..4], 16)?;
-                let b = u8::from_str_radix(&hex[4..6], 16)?;
-                (r, g, b)
-            }
-            _ => (fallback_r, fallback_g, fallback_b),
-        };
+        NSFont::fontWithName_size(&font_name_ns, font_size)
+            .unwrap_or_else(|| NSFont::systemFontOfSize(font_size))
+    }
 
-        Ok(NSColor::colorWithSRGBRed_green_blue_alpha(
-            r as f64 / 255.0,
-            g as f64 / 255.0,
-            b as f64 / 255.0,
-            1.0,
-        ))
-    }
-
-    fn parse_color_with_alpha(
-        hex: &str,
-        fallback_r: u8,
-        fallback_g: u8,
-        fallback_b: u8,
-        alpha: f32,
-    ) -> Result<Retained<NSColor>> {
-        let hex = hex.trim_start_matches('#');
-
-        let (r, g, b) = match hex.len() {
-            3 => {
-                let r = u8::from_str_radix(&hex[0..1].repeat(2), 16)?;
-                let g = u8::from_str_radix(&hex[1..2].repeat(2), 16)?;
-                let b = u8::from_str_radix(&hex[2..3].repeat(2), 16)?;
-                (r, g, b)
-            }
-            6 => {
-                let r = u8::from_str_radix(&hex[0..2], 16)?;
-                let g = u8::from_str_radix(&hex[2..4], 16)?;
-                let b = u8::from_str_radix(&hex[4..6], 16)?;
-                (r, g, b)
-            }
-            _ => (fallback_r, fallback_g, fallback_b),
-        };
+    fn parse_style(key: &str, value: Option<&str>) -> Result<Color> {
+        let value = value.ok_or_else(|| Error::new(format!("{} is not set", key)))?;
+        Color::parse(value).map_err(|e| Error::new(format!("{}: {}", key, e)))
+    }
+
+    fn color(key: &str, value: Option<&str>) -> Result<Retained<NSColor>> {
+        let color = Self::parse_style(key, value)?;
+        Ok(Self::ns_color(color, color.alpha()))
+    }
+
+    fn ns_color(color: Color, alpha: f64) -> Retained<NSColor> {
+        NSColor::colorWithSRGBRed_green_blue_alpha(color.red(), color.green(), color.blue(), alpha)
+    }
+}
+
+/// The effective config as TOML, with defaults filled in and the built-in
+/// sources listed
+pub fn dump(config_path: Option<PathBuf>) -> Result<String> {
+    let path = self::config_path(config_path)?;
+    let mut raw = RawConfig::load(&path)?;
+
+    let sources = SourceRegistry::new(raw.source, &raw.ranking)?;
+    raw.source = sources.all().iter().map(Source::to_config).collect();
+    raw.keys = parse_keys(&raw.keys)?.to_table();
+
+    Ok(toml::to_string(&raw)?)
+}
+
+fn parse_keys(keys: &BTreeMap<String, String>) -> Result<Keymap> {
+    Keymap::new(keys).map_err(|e| Error::new(format!("keys: {}", e)))
+}
+
+/// Query prefixes by scope name, empty ones left out, longest first so the
+/// longest prefix a query starts with wins
+#[cfg(any(target_os = "macos", test))]
+fn parse_prefixes(raw: &BTreeMap<String, String>) -> Result<Vec<(String, Scope)>> {
+    let mut prefixes = Vec::with_capacity(raw.len());
+
+    for (scope, prefix) in raw {
+        if prefix.is_empty() {
+            continue;
+        }
+        let scope = Scope::from_name(scope)
+            .ok_or_else(|| Error::new(format!("Unknown prefix scope: {}", scope)))?;
+        prefixes.push((prefix.clone(), scope));
+    }
+
+    prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    Ok(prefixes)
+}
+
+/// The prefix `query` starts with, if its scope has anything to search, so
+/// `n ` doesn't hide every result when nixpkgs isn't loaded. The calculator
+/// has no elements but answers the query itself.
+#[cfg(any(target_os = "macos", test))]
+pub fn find_prefix<'a>(
+    prefixes: &'a [(String, Scope)],
+    query: &str,
+    elements: &ElementList,
+) -> Option<&'a (String, Scope)> {
+    prefixes.iter().find(|(prefix, scope)| {
+        query.starts_with(prefix.as_str())
+            && (*scope == Scope::only(ElementType::CalculatorResult)
+                || elements
+                    .inner
+                    .iter()
+                    .any(|element| scope.contains(&element.element_type)))
+    })
+}
+
+pub fn config_path(config_path: Option<PathBuf>) -> Result<PathBuf> {
+    match config_path {
+        Some(path) => Ok(path),
+        None => Ok(PathBuf::from(
+            std::env::var("XDG_CONFIG_HOME")
+                .or_else(|_| std::env::var("HOME").map(|home| format!("{}/.cache", home)))
+                .map_err(|_| Error::new("Neither $XDG_CONFIG_HOME or $HOME variables are set."))?,
+        )
+        .join("frisk")
+        .join("config.toml")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::element::Element;
+    use tempfile::TempDir;
+
+    fn load(content: &str) -> RawConfig {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, content).unwrap();
+        RawConfig::load(&path).unwrap()
+    }
+
+    fn element(element_type: ElementType) -> Element {
+        Element {
+            name: "nix".into(),
+            value: "nix".into(),
+            element_type,
+            confirm: false,
+        }
+    }
+
+    fn prefixes(raw: &[(&str, &str)]) -> Result<Vec<(String, Scope)>> {
+        parse_prefixes(
+            &raw.iter()
+                .map(|(scope, prefix)| (scope.to_string(), prefix.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn prefixes_are_parsed_longest_first() {
+        let prefixes = prefixes(&[("apps", "a "), ("commands", ">> "), ("nixpkgs", "")]).unwrap();
+        assert_eq!(
+            prefixes,
+            [
+                (">> ".to_string(), Scope::from_name("commands").unwrap()),
+                ("a ".to_string(), Scope::only(ElementType::Application)),
+            ]
+        );
+
+        let error = self::prefixes(&[("widgets", "w ")]).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown prefix scope: widgets");
+    }
+
+    #[test]
+    fn prefixes_only_scope_to_loaded_elements() {
+        let prefixes = prefixes(&[("nixpkgs", "n "), ("calculator", "= ")]).unwrap();
+        let mut elements = ElementList::new();
+
+        assert_eq!(find_prefix(&prefixes, "n hello", &elements), None);
+        let (_, scope) = find_prefix(&prefixes, "= 1+1", &elements).unwrap();
+        assert_eq!(*scope, Scope::only(ElementType::CalculatorResult));
+
+        elements.add(element(ElementType::NixPackage));
+        let (prefix, _) = find_prefix(&prefixes, "n hello", &elements).unwrap();
+        assert_eq!(prefix, "n ");
+        assert_eq!(find_prefix(&prefixes, "hello", &elements), None);
+    }
+
+    #[test]
+    fn flat_keys_move_into_the_tables() {
+        let raw =
+            load("items = \"#111\"\nmatch = \"#222\"\nwindow_padding = 5\nitem_spacing = 3\n");
+
+        assert_eq!(raw.styles.items.as_deref(), Some("#111"));
+        assert_eq!(raw.styles.match_text.as_deref(), Some("#222"));
+        assert_eq!(raw.spacing.window_padding_x, Some(5.0));
+        assert_eq!(raw.spacing.window_padding_y, Some(5.0));
+        assert_eq!(raw.spacing.item_spacing, Some(3.0));
+        assert_eq!(
+            raw.spacing.prompt_to_items,
+            Spacing::defaults().prompt_to_items
+        );
+        assert_eq!(raw.items, None);
+        assert_eq!(raw.window_padding, None);
+
+        let dumped = toml::to_string(&raw).unwrap();
+        assert!(!dumped.contains("window_padding ="), "{}", dumped);
+    }
+
+    #[test]
+    fn tables_take_precedence_over_flat_keys() {
+        let raw = load(
+            "\
+items = \"#111\"
+caret = \"#333\"
+window_padding = 5
+
+[styles]
+items = \"#222\"
+
+[spacing]
+window_padding_y = 8
+",
+        );
+
+        assert_eq!(raw.styles.items.as_deref(), Some("#222"));
+        assert_eq!(raw.styles.caret.as_deref(), Some("#333"));
+        assert_eq!(raw.spacing.window_padding_x, Some(5.0));
+        assert_eq!(raw.spacing.window_padding_y, Some(8.0));
+    }
+
+    #[test]
+    fn user_colours_take_precedence_over_the_theme() {
+        let theme = theme::load(Path::new("/nonexistent"), "nord").unwrap();
+        let raw = load("theme = \"nord\"\nitems = \"#111\"\n\n[styles]\ncaret = \"#222\"\n");
+
+        assert_eq!(raw.styles.items.as_deref(), Some("#111"));
+        assert_eq!(raw.styles.caret.as_deref(), Some("#222"));
+        assert_eq!(raw.styles.background, theme.background);
+        assert_eq!(raw.styles.match_text, theme.match_text);
+    }
+
+    #[test]
+    fn unset_colours_fall_back_to_the_defaults() {
+        let raw = load("[styles]\nitems = \"#111\"\n");
 
-        Ok(NSColor::colorWithSRGBRed_green_blue_alpha(
-            r as f64 / 255.0,
-            g as f64 / 255.0,
-            b as f64 / 255.0,
-            alpha as f64,
-        ))
+        let defaults = Styles::defaults();
+        assert_eq!(raw.styles.items.as_deref(), Some("#111"));
+        assert_eq!(raw.styles.background, defaults.background);
+        assert_eq!(raw.styles.prompt, None);
+        assert_eq!(
+            raw.spacing.window_padding_x,
+            Spacing::defaults().window_padding_x
+        );
     }
 }
@@ -1,10 +1,18 @@
+use crate::core::commands::{CommandOptions, CommandsConfig};
+use crate::core::history::{self, History};
+use crate::core::source::Ranking;
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 use nucleo_matcher::{
     pattern::{CaseMatching, Normalization, Pattern},
     Config as MatcherConfig, Matcher, Utf32Str,
 };
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
 
-#[derive(Clone, Encode, Decode)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
 pub enum ElementType {
     Application,
     CalculatorResult,
@@ -14,13 +22,109 @@ pub enum ElementType {
     RustCrate,
     HomebrewPackage,
     Dictionary,
+    Text,
+    ScriptFilter,
+    Quicklink,
 }
 
-#[derive(Clone, Encode, Decode)]
+impl ElementType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElementType::Application => "application",
+            ElementType::CalculatorResult => "calculator",
+            ElementType::SystemCommand => "command",
+            ElementType::ClipboardHistory => "clipboard",
+            ElementType::NixPackage => "nixpkg",
+            ElementType::RustCrate => "crate",
+            ElementType::HomebrewPackage => "homebrew",
+            ElementType::Dictionary => "dictionary",
+            ElementType::Text => "text",
+            ElementType::ScriptFilter => "filter",
+            ElementType::Quicklink => "quicklink",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "application" | "apps" => Some(ElementType::Application),
+            "calculator" | "calc" => Some(ElementType::CalculatorResult),
+            "command" | "commands" => Some(ElementType::SystemCommand),
+            "clipboard" | "clip" => Some(ElementType::ClipboardHistory),
+            "nixpkg" | "nixpkgs" | "nix" => Some(ElementType::NixPackage),
+            "crate" => Some(ElementType::RustCrate),
+            "homebrew" | "brew" => Some(ElementType::HomebrewPackage),
+            "dictionary" | "dict" => Some(ElementType::Dictionary),
+            "text" => Some(ElementType::Text),
+            "filter" => Some(ElementType::ScriptFilter),
+            "quicklink" => Some(ElementType::Quicklink),
+            _ => None,
+        }
+    }
+}
+
+/// The element types a prefix or IPC search is limited to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope(Vec<ElementType>);
+
+impl Scope {
+    pub fn contains(&self, element_type: &ElementType) -> bool {
+        self.0.contains(element_type)
+    }
+}
+
+/// Prefixes and IPC searches are answered by the picker
+#[cfg(any(target_os = "macos", test))]
+impl Scope {
+    pub fn only(element_type: ElementType) -> Self {
+        Self(vec![element_type])
+    }
+
+    /// Commands include script filters, which are commands too
+    pub fn from_name(name: &str) -> Option<Self> {
+        match ElementType::from_name(name)? {
+            ElementType::SystemCommand => Some(Self(vec![
+                ElementType::SystemCommand,
+                ElementType::ScriptFilter,
+            ])),
+            element_type => Some(Self::only(element_type)),
+        }
+    }
+
+    /// Shown in the prompt in place of the prefix
+    pub fn label(&self) -> &'static str {
+        self.0[0].as_str()
+    }
+}
+
+#[derive(Clone, Encode)]
 pub struct Element {
     pub name: Box<str>,
     pub value: Box<str>,
     pub element_type: ElementType,
+    /// Ask before running this element
+    pub confirm: bool,
+}
+
+/// Decoded with the cache format version as context, since `confirm` was
+/// added in version 2
+impl Decode<u32> for Element {
+    fn decode<D: Decoder<Context = u32>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let name = Box::<str>::decode(decoder)?;
+        let value = Box::<str>::decode(decoder)?;
+        let element_type = ElementType::decode(decoder)?;
+        let confirm = if *decoder.context() >= 2 {
+            bool::decode(decoder)?
+        } else {
+            false
+        };
+
+        Ok(Self {
+            name,
+            value,
+            element_type,
+            confirm,
+        })
+    }
 }
 
 impl Element {
@@ -29,6 +133,7 @@ impl Element {
             name: name.into_boxed_str(),
             value: value.into_boxed_str(),
             element_type: ElementType::Application,
+            confirm: false,
         }
     }
 
@@ -37,14 +142,17 @@ impl Element {
             name: name.into_boxed_str(),
             value: command.into_boxed_str(),
             element_type: ElementType::SystemCommand,
+            confirm: false,
         }
     }
 
+    #[cfg(any(target_os = "macos", test))]
     pub fn new_clipboard_entry(name: String, value: String) -> Self {
         Self {
             name: name.into_boxed_str(),
             value: value.into_boxed_str(),
             element_type: ElementType::ClipboardHistory,
+            confirm: false,
         }
     }
 
@@ -53,6 +161,7 @@ impl Element {
             name: name.into_boxed_str(),
             value: url.into_boxed_str(),
             element_type: ElementType::HomebrewPackage,
+            confirm: false,
         }
     }
 
@@ -61,6 +170,7 @@ impl Element {
             name: name.into_boxed_str(),
             value: attr_name.into_boxed_str(),
             element_type: ElementType::NixPackage,
+            confirm: false,
         }
     }
 
@@ -69,14 +179,65 @@ impl Element {
             name: name.into_boxed_str(),
             value: value.into_boxed_str(),
             element_type: ElementType::Dictionary,
+            confirm: false,
+        }
+    }
+
+    pub fn new_script_filter(name: String, command: String) -> Self {
+        Self {
+            name: name.into_boxed_str(),
+            value: command.into_boxed_str(),
+            element_type: ElementType::ScriptFilter,
+            confirm: false,
         }
     }
+
+    #[cfg(any(target_os = "macos", test))]
+    pub fn new_quicklink(name: String, url: String) -> Self {
+        Self {
+            name: name.into_boxed_str(),
+            value: url.into_boxed_str(),
+            element_type: ElementType::Quicklink,
+            confirm: false,
+        }
+    }
+
+    pub fn new_text(text: String) -> Self {
+        Self {
+            name: text.clone().into_boxed_str(),
+            value: text.into_boxed_str(),
+            element_type: ElementType::Text,
+            confirm: false,
+        }
+    }
+
+    /// The text the query is matched against
+    fn search_text(&self) -> &str {
+        match self.element_type {
+            ElementType::Dictionary => &self.value,
+            _ => &self.name,
+        }
+    }
+}
+
+pub struct SearchMatch {
+    pub index: usize,
+    pub score: u32,
 }
 
 pub struct ElementList {
     pub inner: Vec<Element>,
     matcher: Matcher,
     char_buf: Vec<char>,
+    history: History,
+    /// Element ranges added together from one source, in insertion order
+    source_ranges: Vec<(Range<usize>, Ranking)>,
+    type_ranking: Vec<(ElementType, Ranking)>,
+    /// How to run commands that set more than an action, by element index
+    command_options: HashMap<usize, CommandOptions>,
+    /// Source names and files this list was loaded from, to load it again
+    pub loaded_sources: Vec<String>,
+    pub loaded_paths: Vec<PathBuf>,
 }
 
 impl ElementList {
@@ -85,24 +246,90 @@ impl ElementList {
             inner: Vec::new(),
             matcher: Matcher::new(MatcherConfig::DEFAULT),
             char_buf: Vec::with_capacity(256),
+            history: History::default(),
+            source_ranges: Vec::new(),
+            type_ranking: Vec::new(),
+            command_options: HashMap::new(),
+            loaded_sources: Vec::new(),
+            loaded_paths: Vec::new(),
         }
     }
 
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    pub fn set_type_ranking(&mut self, ranking: Vec<(ElementType, Ranking)>) {
+        self.type_ranking = ranking;
+    }
+
     pub fn add(&mut self, element: Element) {
         self.inner.push(element);
     }
 
-    pub fn search(&mut self, query: &str) -> Vec<usize> {
+    /// Add all elements of one source, scored and capped by its `ranking`
+    pub fn add_source(&mut self, elements: Vec<Element>, ranking: Ranking) {
+        let start = self.inner.len();
+        self.inner.extend(elements);
+        if ranking != Ranking::default() {
+            self.source_ranges.push((start..self.inner.len(), ranking));
+        }
+    }
+
+    /// Add the commands from a commands.toml along with how to run them
+    pub fn add_commands(&mut self, commands: &CommandsConfig, ranking: Ranking) {
+        let start = self.inner.len();
+        self.add_source(commands.to_elements(), ranking);
+
+        for (offset, command) in commands.command.iter().enumerate() {
+            if command.options != CommandOptions::default() {
+                self.command_options
+                    .insert(start + offset, command.options.clone());
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn command_options(&self, idx: usize) -> Option<&CommandOptions> {
+        self.command_options.get(&idx)
+    }
+
+    fn source_of(&self, idx: usize) -> Option<usize> {
+        let i = self
+            .source_ranges
+            .partition_point(|(range, _)| range.end <= idx);
+        self.source_ranges
+            .get(i)
+            .filter(|(range, _)| range.contains(&idx))
+            .map(|_| i)
+    }
+
+    fn ranking_of_type(&self, element_type: &ElementType) -> Option<(usize, &Ranking)> {
+        self.type_ranking
+            .iter()
+            .enumerate()
+            .find(|(_, (t, _))| t == element_type)
+            .map(|(i, (_, ranking))| (i, ranking))
+    }
+
+    pub fn search(&mut self, query: &str) -> Vec<SearchMatch> {
+        self.search_in(query, None)
+    }
+
+    /// Search only elements in `scope`, or everything when it is `None`
+    pub fn search_in(&mut self, query: &str, scope: Option<&Scope>) -> Vec<SearchMatch> {
         let pattern = Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart);
-        let mut matches: Vec<(usize, u32)> = Vec::with_capacity(20);
+        let mut matches: Vec<SearchMatch> = Vec::with_capacity(20);
         let query_lower = query.to_lowercase();
+        let now = history::now();
+        let use_history = !self.history.is_empty();
 
         for (idx, element) in self.inner.iter().enumerate() {
-            let search_text = match element.element_type {
-                ElementType::Dictionary => &element.value,
-                _ => &element.name,
-            };
+            if scope.is_some_and(|scope| !scope.contains(&element.element_type)) {
+                continue;
+            }
 
+            let search_text = element.search_text();
             let haystack = Utf32Str::new(search_text, &mut self.char_buf);
 
             if let Some(mut score) = pattern.score(haystack, &mut self.matcher) {
@@ -114,21 +341,221 @@ impl ElementList {
                         && text_lower
                             .chars()
                             .nth(query_lower.len())
-                            .map_or(false, |c| c == ' ' || !c.is_alphanumeric())
+                            .is_some_and(|c| c == ' ' || !c.is_alphanumeric())
                     {
                         score = score.saturating_add(5000);
                     }
                 }
 
-                matches.push((idx, score));
+                if use_history {
+                    score = score.saturating_add(self.history.bonus(element, now));
+                }
+
+                if let Some(i) = self.source_of(idx) {
+                    score = self.source_ranges[i].1.apply(score);
+                }
+                if let Some((_, ranking)) = self.ranking_of_type(&element.element_type) {
+                    score = ranking.apply(score);
+                }
+
+                matches.push(SearchMatch { index: idx, score });
             }
         }
 
-        matches.sort_unstable_by(|a, b| b.1.cmp(&a.1));
-        matches.into_iter().map(|(idx, _)| idx).collect()
+        // Stable so ties (e.g. everything on an empty query) keep insertion order
+        matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+        self.apply_caps(&mut matches);
+        matches
+    }
+
+    /// Matched character positions in an element's name as it is drawn,
+    /// sorted and deduplicated. Only worked out for the rows that are drawn,
+    /// as it costs more than scoring.
+    #[cfg(any(target_os = "macos", test))]
+    pub fn match_positions(&mut self, query: &str, idx: usize) -> Vec<u32> {
+        let Some(element) = self.inner.get(idx) else {
+            return Vec::new();
+        };
+        let pattern = Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart);
+        // Dictionary entries are searched by word but show its definition too
+        let haystack = Utf32Str::new(&element.name, &mut self.char_buf);
+
+        let mut positions = Vec::new();
+        pattern.indices(haystack, &mut self.matcher, &mut positions);
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+
+    /// Drop matches beyond each source's and element type's `max_results`
+    fn apply_caps(&self, matches: &mut Vec<SearchMatch>) {
+        let has_caps = self
+            .source_ranges
+            .iter()
+            .map(|(_, ranking)| ranking)
+            .chain(self.type_ranking.iter().map(|(_, ranking)| ranking))
+            .any(|ranking| ranking.max_results.is_some());
+        if !has_caps {
+            return;
+        }
+
+        let mut source_counts = vec![0; self.source_ranges.len()];
+        let mut type_counts = vec![0; self.type_ranking.len()];
+        let full =
+            |count: usize, ranking: &Ranking| ranking.max_results.is_some_and(|max| count >= max);
+
+        matches.retain(|m| {
+            let source = self.source_of(m.index);
+            let element_type = self.ranking_of_type(&self.inner[m.index].element_type);
+
+            if source.is_some_and(|i| full(source_counts[i], &self.source_ranges[i].1))
+                || element_type.is_some_and(|(i, ranking)| full(type_counts[i], ranking))
+            {
+                return false;
+            }
+
+            if let Some(i) = source {
+                source_counts[i] += 1;
+            }
+            if let Some((i, _)) = element_type {
+                type_counts[i] += 1;
+            }
+            true
+        });
     }
 
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 }
+
+/// Character positions as ranges of UTF-16 offsets, which NSString uses,
+/// with adjacent positions merged
+#[cfg(any(target_os = "macos", test))]
+pub fn highlight_ranges(text: &str, positions: &[u32]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::with_capacity(positions.len());
+    let mut positions = positions.iter().peekable();
+    let mut offset = 0;
+
+    for (i, c) in text.chars().enumerate() {
+        let Some(&&next) = positions.peek() else {
+            break;
+        };
+
+        let len = c.len_utf16();
+        if next as usize == i {
+            positions.next();
+            match ranges.last_mut() {
+                Some(last) if last.end == offset => last.end += len,
+                _ => ranges.push(offset..offset + len),
+            }
+        }
+        offset += len;
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranking(multiplier: f64, bias: i32, max_results: Option<usize>) -> Ranking {
+        Ranking {
+            multiplier,
+            bias,
+            max_results,
+        }
+    }
+
+    fn search(list: &mut ElementList, query: &str) -> Vec<String> {
+        list.search(query)
+            .iter()
+            .map(|m| list.inner[m.index].name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ranking_scales_then_adds_bias() {
+        assert_eq!(ranking(0.5, -10, None).apply(100), 40);
+        assert_eq!(ranking(1.0, -500, None).apply(100), 0);
+        assert_eq!(ranking(2.0, 0, None).apply(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn commands_scope_includes_script_filters() {
+        let mut list = ElementList::new();
+        list.add(Element::new_system_command(
+            "Sleep".to_string(),
+            "pmset sleepnow".to_string(),
+        ));
+        list.add(Element::new_script_filter(
+            "Branches".to_string(),
+            "git branch".to_string(),
+        ));
+        list.add(Element::new(
+            "Safari".to_string(),
+            "/Applications/Safari.app".to_string(),
+        ));
+
+        let commands = Scope::from_name("commands").unwrap();
+        assert_eq!(commands.label(), "command");
+        assert_eq!(list.search_in("", Some(&commands)).len(), 2);
+
+        let apps = Scope::from_name("apps").unwrap();
+        assert_eq!(list.search_in("", Some(&apps)).len(), 1);
+        assert_eq!(Scope::from_name("widgets"), None);
+    }
+
+    #[test]
+    fn rankings_reorder_and_cap_results() {
+        let clip = |name: &str| Element::new_clipboard_entry(name.to_string(), name.to_string());
+        let text = |name: &str| Element::new_text(name.to_string());
+
+        let mut list = ElementList::new();
+        list.add_source(
+            vec![
+                Element::new("ax".to_string(), "/Applications/ax.app".to_string()),
+                Element::new("ay".to_string(), "/Applications/ay.app".to_string()),
+            ],
+            Ranking::default(),
+        );
+        list.add_source(
+            vec![clip("a1"), clip("a2"), clip("a3")],
+            ranking(2.0, 0, Some(2)),
+        );
+        list.add_source(vec![text("a4"), text("a5")], Ranking::default());
+        list.set_type_ranking(vec![(ElementType::Text, ranking(1.0, 1000, Some(1)))]);
+
+        assert_eq!(search(&mut list, "a"), ["a4", "a1", "a2", "ax", "ay"]);
+    }
+
+    #[test]
+    fn highlight_ranges_are_utf16_offsets() {
+        assert_eq!(highlight_ranges("code", &[0, 1, 3]), [0..2, 3..4]);
+        assert_eq!(highlight_ranges("Café Über", &[3, 5, 6]), [3..4, 5..7]);
+        // Outside the basic plane a character takes two UTF-16 units
+        assert_eq!(
+            highlight_ranges("😀 emoji 😀", &[0, 2, 8]),
+            [0..2, 3..4, 9..11]
+        );
+        assert_eq!(highlight_ranges("short", &[9]), []);
+    }
+
+    #[test]
+    fn match_positions_are_in_the_drawn_name() {
+        let mut list = ElementList::new();
+        list.add(Element::new_text("Visual Studio Code".into()));
+        list.add(Element::new_text("Café Über".into()));
+        list.add(Element::new_dictionary(
+            "fire - the event of something burning".into(),
+            "fire".into(),
+        ));
+
+        assert_eq!(list.match_positions("vsc", 0), [0, 7, 14]);
+        assert_eq!(list.match_positions("über", 1), [5, 6, 7, 8]);
+        assert_eq!(list.match_positions("ube", 1), [5, 6, 7]);
+        assert_eq!(list.match_positions("fire", 2), [0, 1, 2, 3]);
+        assert_eq!(list.match_positions("fire", 3), Vec::<u32>::new());
+    }
+}
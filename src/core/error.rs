@@ -33,6 +33,12 @@ impl From<toml::de::Error> for Error {
     }
 }
 
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::new(format!("TOML error: {}", err))
+    }
+}
+
 impl From<bincode::error::DecodeError> for Error {
     fn from(err: bincode::error::DecodeError) -> Self {
         Self::new(format!("Bincode decode error: {}", err))
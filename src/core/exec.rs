@@ -0,0 +1,410 @@
+use crate::core::commands::CommandOptions;
+use crate::core::error::{Error, Result};
+use crate::core::placeholder::{is_env_name, shell_quote};
+use crate::loader::expand_home;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long to keep reading output after the process exits. Anything it left
+/// running in the background may hold the pipes open for much longer.
+const DRAIN_GRACE: Duration = Duration::from_millis(200);
+
+/// The process for `action`, run by the configured shell in its cwd and env.
+/// It leads a process group of its own, so a timeout can kill everything it
+/// started.
+pub fn build(action: &str, options: &CommandOptions) -> Command {
+    let mut command = Command::new(options.shell.as_deref().unwrap_or("sh"));
+    command
+        .arg("-c")
+        .arg(action)
+        .envs(&options.env)
+        .process_group(0);
+    if let Some(cwd) = &options.cwd {
+        command.current_dir(expand_home(cwd));
+    }
+    command
+}
+
+/// A process waited for on another thread, so the picker keeps drawing
+pub struct Background {
+    rx: Receiver<Result<Output>>,
+}
+
+impl Background {
+    /// Wait for `child`, killing it after `timeout`. Whatever it writes to
+    /// piped stdout and stderr is collected.
+    pub fn wait(child: Child, timeout: Option<Duration>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(wait_with_timeout(child, timeout));
+        });
+        Self { rx }
+    }
+
+    /// The output once the process has exited, without blocking
+    pub fn finished(&self) -> Option<Result<Output>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(Error::new("Stopped waiting for the process")))
+            }
+        }
+    }
+}
+
+fn wait_with_timeout(mut child: Child, timeout: Option<Duration>) -> Result<Output> {
+    let stdout = child.stdout.take().map(Drain::start);
+    let stderr = child.stderr.take().map(Drain::start);
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+            let _ = child.wait();
+            return Err(Error::new(format!(
+                "Timed out after {}s",
+                timeout.unwrap_or_default().as_secs()
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let finish = |drain: Option<Drain>| drain.map(Drain::finish).unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout: finish(stdout),
+        stderr: finish(stderr),
+    })
+}
+
+/// A pipe read on another thread so a chatty process can't fill it
+struct Drain {
+    output: Arc<Mutex<Vec<u8>>>,
+    closed: Receiver<()>,
+}
+
+impl Drain {
+    fn start(mut reader: impl Read + Send + 'static) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let (tx, closed) = mpsc::channel();
+
+        let buffer = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            loop {
+                match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => buffer.lock().unwrap().extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            let _ = tx.send(());
+        });
+
+        Self { output, closed }
+    }
+
+    /// What was read once the pipe closes, or after `DRAIN_GRACE` if it stays
+    /// open
+    fn finish(self) -> Vec<u8> {
+        let _ = self.closed.recv_timeout(DRAIN_GRACE);
+        let output = std::mem::take(&mut *self.output.lock().unwrap());
+        output
+    }
+}
+
+/// Run `action` as its options ask. Without `wait` this returns once the
+/// process has started, otherwise with the process to wait for.
+pub fn run(action: &str, options: &CommandOptions, terminal: &str) -> Result<Option<Background>> {
+    if options.run_in_terminal {
+        run_in_terminal(action, options, terminal)?;
+        return Ok(None);
+    }
+
+    let wait = options.wait || options.timeout.is_some();
+    let mut command = build(action, options);
+    if wait {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+    }
+
+    let child = command
+        .spawn()
+        .map_err(|e| Error::new(format!("Failed to execute: {}", e)))?;
+
+    if !wait {
+        return Ok(None);
+    }
+    Ok(Some(Background::wait(
+        child,
+        options.timeout.map(Duration::from_secs),
+    )))
+}
+
+/// Whether a waited for command succeeded, with the last line of its stderr
+/// when it didn't
+pub fn check(result: Result<Output>) -> Result<()> {
+    let output = result?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => Err(Error::new(format!("{}: {}", output.status, line.trim()))),
+        None => Err(Error::new(output.status.to_string())),
+    }
+}
+
+/// Open `action` in the configured terminal, or Terminal.app when none is set.
+/// `{command}` in the terminal template becomes a shell-quoted command line.
+fn run_in_terminal(action: &str, options: &CommandOptions, terminal: &str) -> Result<()> {
+    let line = terminal_line(action, options)?;
+
+    let mut command = if terminal.is_empty() {
+        let script = line.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = Command::new("osascript");
+        command
+            .arg("-e")
+            .arg(format!(
+                "tell application \"Terminal\" to do script \"{}\"",
+                script
+            ))
+            .arg("-e")
+            .arg("tell application \"Terminal\" to activate");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(terminal.replace("{command}", &shell_quote(&line)));
+        command
+    };
+
+    command
+        .spawn()
+        .map_err(|e| Error::new(format!("Failed to open terminal: {}", e)))?;
+    Ok(())
+}
+
+/// The shell command line a terminal runs for `action`, in its cwd and env
+fn terminal_line(action: &str, options: &CommandOptions) -> Result<String> {
+    let mut line = String::new();
+    if let Some(cwd) = &options.cwd {
+        line.push_str(&format!(
+            "cd {} && ",
+            shell_quote(&expand_home(cwd).to_string_lossy())
+        ));
+    }
+    for (key, value) in &options.env {
+        if !is_env_name(key) {
+            return Err(Error::new(format!(
+                "Invalid environment variable name: {}",
+                key
+            )));
+        }
+        line.push_str(&format!("{}={} ", key, shell_quote(value)));
+    }
+    line.push_str(&format!(
+        "{} -c {}",
+        options.shell.as_deref().unwrap_or("sh"),
+        shell_quote(action)
+    ));
+
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn wait(action: &str, options: &CommandOptions) -> Result<Output> {
+        let child = build(action, options)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        wait_with_timeout(child, options.timeout.map(Duration::from_secs))
+    }
+
+    /// Poll like the picker does until the process exits
+    fn finished(process: &Background) -> Result<Output> {
+        let start = Instant::now();
+        loop {
+            if let Some(result) = process.finished() {
+                return result;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// What `sh` prints running a terminal's command line
+    fn run_line(action: &str, options: &CommandOptions) -> String {
+        let line = terminal_line(action, options).unwrap();
+        let output = Command::new("sh").arg("-c").arg(&line).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn output_is_collected() {
+        let output = wait("echo out; echo err >&2", &CommandOptions::default()).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn failure_reports_the_last_line_of_stderr() {
+        let result = wait(
+            "echo first >&2; echo 'last line' >&2; echo >&2; exit 3",
+            &CommandOptions::default(),
+        );
+        let error = check(result).err().unwrap().to_string();
+        assert!(error.ends_with(": last line"), "{error}");
+        assert!(error.contains('3'), "{error}");
+
+        assert_eq!(
+            check(wait("exit 4", &CommandOptions::default()))
+                .err()
+                .unwrap()
+                .to_string(),
+            "exit status: 4"
+        );
+    }
+
+    #[test]
+    fn timeout_kills_everything_the_command_started() {
+        let dir = TempDir::new().unwrap();
+        let marker = dir.path().join("marker");
+        let child = build(
+            &format!(
+                "(sleep 1; touch {}) & sleep 10",
+                shell_quote(&marker.to_string_lossy())
+            ),
+            &CommandOptions::default(),
+        )
+        .spawn()
+        .unwrap();
+
+        let start = Instant::now();
+        let error = wait_with_timeout(child, Some(Duration::from_millis(200)))
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Timed out"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn background_process_holding_the_pipes_does_not_block() {
+        let start = Instant::now();
+        let output = wait("sleep 5 & echo started", &CommandOptions::default()).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"started\n");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn background_reports_when_finished() {
+        let child = build("exit 0", &CommandOptions::default()).spawn().unwrap();
+        let result = finished(&Background::wait(child, None));
+        assert!(result.unwrap().status.success());
+    }
+
+    #[test]
+    fn waited_command_reports_failure() {
+        let options = CommandOptions {
+            wait: true,
+            ..Default::default()
+        };
+        let process = run("echo broken >&2; exit 1", &options, "")
+            .unwrap()
+            .unwrap();
+        let result = finished(&process);
+        assert!(check(result).unwrap_err().to_string().ends_with(": broken"));
+    }
+
+    #[test]
+    fn terminal_template_runs_the_command_line() {
+        let dir = TempDir::new().unwrap();
+        let options = CommandOptions {
+            cwd: Some(dir.path().to_string_lossy().into_owned()),
+            run_in_terminal: true,
+            ..Default::default()
+        };
+        assert!(run("touch marker", &options, "sh -c {command}")
+            .unwrap()
+            .is_none());
+
+        let marker = dir.path().join("marker");
+        let start = Instant::now();
+        while !marker.exists() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn terminal_line_keeps_cwd_env_and_action_literal() {
+        let dir = TempDir::new().unwrap();
+        let cwd = dir.path().join("it's $HOME");
+        std::fs::create_dir(&cwd).unwrap();
+        let options = CommandOptions {
+            cwd: Some(cwd.to_string_lossy().into_owned()),
+            env: HashMap::from([("GREETING".into(), "it's $(whoami)".into())]),
+            ..Default::default()
+        };
+
+        let output = run_line(r#"pwd; printf '%s\n' "$GREETING""#, &options);
+        assert_eq!(
+            output,
+            format!("{}\nit's $(whoami)\n", cwd.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn terminal_line_uses_the_shell() {
+        let options = CommandOptions {
+            shell: Some("bash".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            terminal_line("echo hi", &options).unwrap(),
+            "bash -c 'echo hi'"
+        );
+    }
+
+    #[test]
+    fn terminal_line_rejects_env_names_that_would_run() {
+        let options = CommandOptions {
+            env: HashMap::from([("FOO;touch pwned".into(), "x".into())]),
+            ..Default::default()
+        };
+        let error = terminal_line("true", &options).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid environment variable name: FOO;touch pwned"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::core::element::{Element, ElementType};
+use crate::core::error::Result;
+use bincode::{Decode, Encode};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "history.bin";
+#[cfg(any(target_os = "macos", test))]
+const MAX_ENTRIES: usize = 500;
+
+// A launch is worth half as much after a week without use
+const HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
+// Score bonus per decayed launch. The cap is above most nucleo scores (tens
+// to low hundreds), so a frequently launched item beats better matches, but
+// stays under the boosts for dictionary words matching the query.
+const FRECENCY_WEIGHT: f64 = 25.0;
+const MAX_BONUS: f64 = 300.0;
+
+#[derive(Clone, Encode, Decode)]
+pub struct HistoryEntry {
+    pub element_type: ElementType,
+    pub value: Box<str>,
+    /// Launch count with exponential decay applied up to `last_used`
+    pub score: f64,
+    pub last_used: u64,
+}
+
+impl HistoryEntry {
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used) as f64;
+        self.score * 0.5f64.powf(age / HALF_LIFE_SECS)
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    // Keyed by value so lookups during search don't allocate
+    index: HashMap<Box<str>, Vec<usize>>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        Self::from_loaded(crate::loader::load_cache(HISTORY_FILE))
+    }
+
+    fn from_loaded(loaded: Result<Option<Vec<HistoryEntry>>>) -> Self {
+        match loaded {
+            Ok(entries) => Self::from_entries(entries.unwrap_or_default()),
+            Err(e) => {
+                crate::log!("Failed to load launch history: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn from_entries(entries: Vec<HistoryEntry>) -> Self {
+        let mut index: HashMap<Box<str>, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            index.entry(entry.value.clone()).or_default().push(i);
+        }
+        Self { entries, index }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn find(&self, element_type: &ElementType, value: &str) -> Option<&HistoryEntry> {
+        self.index
+            .get(value)?
+            .iter()
+            .map(|&i| &self.entries[i])
+            .find(|entry| &entry.element_type == element_type)
+    }
+
+    pub fn bonus(&self, element: &Element, now: u64) -> u32 {
+        match self.find(&element.element_type, &element.value) {
+            Some(entry) => (entry.frecency(now) * FRECENCY_WEIGHT).min(MAX_BONUS) as u32,
+            None => 0,
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl History {
+    fn record(&mut self, element: &Element, now: u64) {
+        let existing = self
+            .entries
+            .iter_mut()
+            .find(|e| e.element_type == element.element_type && e.value == element.value);
+
+        match existing {
+            Some(entry) => {
+                entry.score = entry.frecency(now) + 1.0;
+                entry.last_used = now;
+            }
+            None => self.entries.push(HistoryEntry {
+                element_type: element.element_type.clone(),
+                value: element.value.clone(),
+                score: 1.0,
+                last_used: now,
+            }),
+        }
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries
+                .sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+            self.entries.truncate(MAX_ENTRIES);
+        }
+    }
+}
+
+/// Record a launch of `element` in the persisted history
+#[cfg(target_os = "macos")]
+pub fn record(element: &Element) -> Result<()> {
+    let lock = crate::cache::lock_cache(HISTORY_FILE)?;
+    let mut history = History::from_loaded(crate::loader::load_cache_locked(HISTORY_FILE, &lock));
+    history.record(element, now());
+    crate::cache::save_cache(HISTORY_FILE, &history.entries)?;
+    crate::log!("Recorded launch of {}", element.name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::element::ElementList;
+
+    fn entry(element: &Element, score: f64, last_used: u64) -> HistoryEntry {
+        HistoryEntry {
+            element_type: element.element_type.clone(),
+            value: element.value.clone(),
+            score,
+            last_used,
+        }
+    }
+
+    fn app(name: &str) -> Element {
+        Element::new(name.to_string(), format!("/Applications/{name}.app"))
+    }
+
+    fn search(elements: Vec<Element>, entries: Vec<HistoryEntry>, query: &str) -> Vec<String> {
+        let mut list = ElementList::new();
+        list.set_history(History::from_entries(entries));
+        for element in elements {
+            list.add(element);
+        }
+        list.search(query)
+            .iter()
+            .map(|m| list.inner[m.index].name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn launches_halve_every_half_life() {
+        let now = 10_000_000;
+        let entry = entry(&app("Safari"), 4.0, now);
+        assert_eq!(entry.frecency(now), 4.0);
+        assert_eq!(entry.frecency(now + HALF_LIFE_SECS as u64), 2.0);
+        assert_eq!(entry.frecency(now + 2 * HALF_LIFE_SECS as u64), 1.0);
+    }
+
+    #[test]
+    fn record_decays_the_old_score_before_adding_a_launch() {
+        let safari = app("Safari");
+        let mut history = History::default();
+        history.record(&safari, 0);
+        history.record(&safari, HALF_LIFE_SECS as u64);
+
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].score, 1.5);
+        assert_eq!(history.entries[0].last_used, HALF_LIFE_SECS as u64);
+    }
+
+    #[test]
+    fn bonus_is_capped() {
+        let safari = app("Safari");
+        let history = History::from_entries(vec![entry(&safari, 1000.0, 0)]);
+        assert_eq!(history.bonus(&safari, 0), MAX_BONUS as u32);
+        assert_eq!(history.bonus(&app("Finder"), 0), 0);
+    }
+
+    #[test]
+    fn empty_query_lists_the_most_frecent_first() {
+        let (a, b, c) = (app("Alpha"), app("Bravo"), app("Charlie"));
+        let now = now();
+        let entries = vec![entry(&c, 1.0, now), entry(&b, 5.0, now)];
+
+        assert_eq!(
+            search(vec![a, b, c], entries, ""),
+            ["Bravo", "Charlie", "Alpha"]
+        );
+    }
+
+    #[test]
+    fn frecent_item_beats_a_better_match() {
+        let (finder, firefox) = (app("Finder"), app("Firefox"));
+        let elements = vec![finder.clone(), firefox];
+        let entries = vec![entry(&finder, 20.0, now())];
+
+        assert_eq!(search(elements.clone(), Vec::new(), "fir")[0], "Firefox");
+        assert_eq!(search(elements, entries, "fir")[0], "Finder");
+    }
+
+    #[test]
+    fn dictionary_word_beats_the_largest_bonus() {
+        let firefox = app("Firefox");
+        let word = Element::new_dictionary("fire".to_string(), "fire".to_string());
+        let entries = vec![entry(&firefox, 1000.0, now())];
+
+        assert_eq!(search(vec![firefox, word], entries, "fire")[0], "fire");
+    }
+}
@@ -0,0 +1,429 @@
+use crate::core::error::{Error, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const DEFAULT_BINDINGS: [(&str, &str); 17] = [
+    ("escape", "cancel"),
+    ("enter", "execute"),
+    // Enter ran the selection whatever was held before keys were configurable
+    ("ctrl+enter", "execute"),
+    ("cmd+enter", "execute"),
+    ("ctrl+y", "execute"),
+    ("tab", "autocomplete"),
+    ("up", "nav_up"),
+    ("down", "nav_down"),
+    ("ctrl+p", "nav_up"),
+    ("ctrl+n", "nav_down"),
+    ("left", "move_left"),
+    ("right", "move_right"),
+    ("backspace", "delete_char"),
+    ("ctrl+backspace", "delete_word"),
+    ("ctrl+w", "delete_word"),
+    ("ctrl+u", "delete_to_start"),
+    ("cmd+v", "paste"),
+];
+
+/// Something the picker does in response to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NavUp,
+    NavDown,
+    MoveLeft,
+    MoveRight,
+    DeleteChar,
+    DeleteWord,
+    DeleteToStart,
+    Autocomplete,
+    Paste,
+    /// Run or print the selection
+    Execute,
+    /// Print the typed query instead of the selection in dmenu mode
+    ExecuteAlt,
+    /// Back out of a confirmation or command input, otherwise quit
+    Cancel,
+    Quit,
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nav_up" => Some(Action::NavUp),
+            "nav_down" => Some(Action::NavDown),
+            "move_left" => Some(Action::MoveLeft),
+            "move_right" => Some(Action::MoveRight),
+            "delete_char" => Some(Action::DeleteChar),
+            "delete_word" => Some(Action::DeleteWord),
+            "delete_to_start" => Some(Action::DeleteToStart),
+            "autocomplete" => Some(Action::Autocomplete),
+            "paste" => Some(Action::Paste),
+            "execute" => Some(Action::Execute),
+            "execute_alt" => Some(Action::ExecuteAlt),
+            "cancel" => Some(Action::Cancel),
+            "quit" => Some(Action::Quit),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::NavUp => "nav_up",
+            Action::NavDown => "nav_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::DeleteChar => "delete_char",
+            Action::DeleteWord => "delete_word",
+            Action::DeleteToStart => "delete_to_start",
+            Action::Autocomplete => "autocomplete",
+            Action::Paste => "paste",
+            Action::Execute => "execute",
+            Action::ExecuteAlt => "execute_alt",
+            Action::Cancel => "cancel",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// An action name, or `none` to unbind a chord
+    pub fn parse(name: &str) -> Result<Option<Self>> {
+        if name == "none" {
+            return Ok(None);
+        }
+        Self::from_name(name)
+            .map(Some)
+            .ok_or_else(|| Error::new(format!("unknown action `{}`", name)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+impl Key {
+    /// The named key for a macOS virtual key code
+    #[cfg(target_os = "macos")]
+    pub fn from_key_code(code: u16) -> Option<Self> {
+        match code {
+            36 => Some(Key::Enter),
+            48 => Some(Key::Tab),
+            51 => Some(Key::Backspace),
+            53 => Some(Key::Escape),
+            115 => Some(Key::Home),
+            116 => Some(Key::PageUp),
+            117 => Some(Key::Delete),
+            119 => Some(Key::End),
+            121 => Some(Key::PageDown),
+            123 => Some(Key::Left),
+            124 => Some(Key::Right),
+            125 => Some(Key::Down),
+            126 => Some(Key::Up),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "escape" | "esc" => Some(Key::Escape),
+            "enter" | "return" => Some(Key::Enter),
+            "tab" => Some(Key::Tab),
+            "space" => Some(Key::Char(' ')),
+            "backspace" => Some(Key::Backspace),
+            "delete" | "del" => Some(Key::Delete),
+            "left" => Some(Key::Left),
+            "right" => Some(Key::Right),
+            "up" => Some(Key::Up),
+            "down" => Some(Key::Down),
+            "home" => Some(Key::Home),
+            "end" => Some(Key::End),
+            "pageup" => Some(Key::PageUp),
+            "pagedown" => Some(Key::PageDown),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Key::Char(c)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        let name = match self {
+            Key::Char(' ') => "space",
+            Key::Char(c) => return c.to_string(),
+            Key::Escape => "escape",
+            Key::Enter => "enter",
+            Key::Tab => "tab",
+            Key::Backspace => "backspace",
+            Key::Delete => "delete",
+            Key::Left => "left",
+            Key::Right => "right",
+            Key::Up => "up",
+            Key::Down => "down",
+            Key::Home => "home",
+            Key::End => "end",
+            Key::PageUp => "pageup",
+            Key::PageDown => "pagedown",
+        };
+        name.to_string()
+    }
+}
+
+/// A key with the modifiers held, written like `ctrl+shift+n`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub cmd: bool,
+    pub key: Key,
+}
+
+impl Chord {
+    pub fn parse(chord: &str) -> Result<Self> {
+        let lower = chord.to_lowercase();
+        // `+` can be bound as the last key, as in `ctrl++`, or on its own
+        let (modifiers, key) = match lower.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None if lower == "+" => ("", "+"),
+            None => match lower.rsplit_once('+') {
+                Some((modifiers, key)) => (modifiers, key),
+                None => ("", lower.as_str()),
+            },
+        };
+
+        let key = Key::from_name(key)
+            .ok_or_else(|| Error::new(format!("unknown key `{}` in `{}`", key, chord)))?;
+        let mut parsed = Chord {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            cmd: false,
+            key,
+        };
+
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier {
+                "ctrl" | "control" => parsed.ctrl = true,
+                "alt" | "option" | "opt" => parsed.alt = true,
+                "shift" => parsed.shift = true,
+                "cmd" | "command" | "super" => parsed.cmd = true,
+                _ => {
+                    return Err(Error::new(format!(
+                        "unknown modifier `{}` in `{}`",
+                        modifier, chord
+                    )))
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "ctrl+"),
+            (self.alt, "alt+"),
+            (self.shift, "shift+"),
+            (self.cmd, "cmd+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key.name())
+    }
+}
+
+/// Chords bound to actions, the defaults with `[keys]` on top
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Chord, Action>,
+}
+
+impl Keymap {
+    pub fn new(keys: &BTreeMap<String, String>) -> Result<Self> {
+        let mut bindings = HashMap::new();
+
+        for (chord, action) in DEFAULT_BINDINGS {
+            let action = Action::from_name(action).expect("default bindings name actions");
+            bindings.insert(Chord::parse(chord)?, action);
+        }
+
+        for (chord, action) in keys {
+            let chord = Chord::parse(chord)?;
+            match Action::parse(action)? {
+                Some(action) => bindings.insert(chord, action),
+                None => bindings.remove(&chord),
+            };
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// Every binding as `chord = action`, sorted by chord
+    pub fn to_table(&self) -> BTreeMap<String, String> {
+        self.bindings
+            .iter()
+            .map(|(chord, action)| (chord.to_string(), action.name().to_string()))
+            .collect()
+    }
+}
+
+/// Used while the picker handles keys
+#[cfg(any(target_os = "macos", test))]
+impl Keymap {
+    /// The action for a chord. Named keys held with only shift or alt fall
+    /// back to the bare key, so shift+backspace still deletes.
+    pub fn action(&self, chord: &Chord) -> Option<Action> {
+        if let Some(action) = self.bindings.get(chord) {
+            return Some(*action);
+        }
+        if matches!(chord.key, Key::Char(_)) || chord.ctrl || chord.cmd {
+            return None;
+        }
+        let bare = Chord {
+            alt: false,
+            shift: false,
+            ..*chord
+        };
+        self.bindings.get(&bare).copied()
+    }
+
+    /// The simplest chord bound to `action`, for hints in the picker
+    pub fn chord_for(&self, action: Action) -> Option<Chord> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(chord, _)| *chord)
+            .min_by_key(|chord| {
+                let modifiers = [chord.ctrl, chord.alt, chord.shift, chord.cmd];
+                (
+                    modifiers.iter().filter(|held| **held).count(),
+                    chord.to_string(),
+                )
+            })
+    }
+
+    /// How to answer a confirmation, e.g. `enter to confirm, escape to cancel`
+    pub fn confirm_hint(&self) -> String {
+        [(Action::Execute, "confirm"), (Action::Cancel, "cancel")]
+            .into_iter()
+            .filter_map(|(action, verb)| Some(format!("{} to {}", self.chord_for(action)?, verb)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(keys: &[(&str, &str)]) -> Keymap {
+        let keys = keys
+            .iter()
+            .map(|(chord, action)| (chord.to_string(), action.to_string()))
+            .collect();
+        Keymap::new(&keys).unwrap()
+    }
+
+    fn chord(chord: &str) -> Chord {
+        Chord::parse(chord).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn chords_parse_modifiers_and_keys() {
+        let parsed = chord("Ctrl+Shift+N");
+        assert!(parsed.ctrl && parsed.shift && !parsed.alt && !parsed.cmd);
+        assert_eq!(parsed.key, Key::Char('n'));
+
+        assert_eq!(chord("option+esc"), chord("alt+escape"));
+        assert_eq!(chord("cmd+space").key, Key::Char(' '));
+        assert_eq!(chord("ctrl+shift+n").to_string(), "ctrl+shift+n");
+    }
+
+    #[test]
+    fn plus_can_be_the_key() {
+        let parsed = chord("ctrl++");
+        assert!(parsed.ctrl);
+        assert_eq!(parsed.key, Key::Char('+'));
+        assert_eq!(chord("+").key, Key::Char('+'));
+        assert_eq!(parsed.to_string(), "ctrl++");
+    }
+
+    #[test]
+    fn unknown_keys_and_modifiers_are_errors() {
+        let error = Chord::parse("ctrl+enterr").unwrap_err().to_string();
+        assert_eq!(error, "unknown key `enterr` in `ctrl+enterr`");
+        let error = Chord::parse("hyper+k").unwrap_err().to_string();
+        assert_eq!(error, "unknown modifier `hyper` in `hyper+k`");
+        let error = Action::parse("explode").unwrap_err().to_string();
+        assert_eq!(error, "unknown action `explode`");
+    }
+
+    #[test]
+    fn none_unbinds_a_default() {
+        let keymap = keymap(&[("ctrl+y", "none"), ("ctrl+j", "nav_down")]);
+        assert_eq!(keymap.action(&chord("ctrl+y")), None);
+        assert_eq!(keymap.action(&chord("enter")), Some(Action::Execute));
+        assert_eq!(keymap.action(&chord("ctrl+j")), Some(Action::NavDown));
+    }
+
+    #[test]
+    fn shift_and_alt_fall_back_to_named_keys_only() {
+        let keymap = keymap(&[]);
+        assert_eq!(
+            keymap.action(&chord("shift+backspace")),
+            Some(Action::DeleteChar)
+        );
+        assert_eq!(keymap.action(&chord("ctrl+tab")), None);
+        assert_eq!(keymap.action(&chord("shift+p")), None);
+    }
+
+    #[test]
+    fn every_default_binding_resolves() {
+        let keymap = keymap(&[]);
+        for (chord, action) in DEFAULT_BINDINGS {
+            assert_eq!(
+                keymap.action(&self::chord(chord)),
+                Action::from_name(action),
+                "{} = {}",
+                chord,
+                action
+            );
+            assert!(Action::from_name(action).is_some(), "{}", action);
+        }
+        assert_eq!(keymap.action(&chord("ctrl+enter")), Some(Action::Execute));
+    }
+
+    #[test]
+    fn confirm_hint_uses_the_bound_chords() {
+        assert_eq!(
+            keymap(&[]).confirm_hint(),
+            "enter to confirm, escape to cancel"
+        );
+
+        let rebound = keymap(&[("enter", "none"), ("ctrl+g", "cancel"), ("escape", "quit")]);
+        assert_eq!(
+            rebound.confirm_hint(),
+            "cmd+enter to confirm, ctrl+g to cancel"
+        );
+
+        let unbound = keymap(&[("escape", "none")]);
+        assert_eq!(unbound.confirm_hint(), "enter to confirm");
+    }
+}
@@ -1,5 +1,19 @@
 pub mod calculator;
+pub mod check;
+pub mod color;
 pub mod commands;
 pub mod config;
 pub mod element;
 pub mod error;
+#[cfg(any(target_os = "macos", test))]
+pub mod exec;
+pub mod history;
+pub mod keymap;
+#[cfg(any(target_os = "macos", test))]
+pub mod placeholder;
+pub mod quicklink;
+pub mod script;
+pub mod source;
+pub mod theme;
+#[cfg(any(target_os = "macos", test))]
+pub mod watch;
@@ -0,0 +1,178 @@
+/// What an action can take values from
+const PLACEHOLDERS: [&str; 3] = ["{query}", "{input}", "{clipboard}"];
+
+/// Whether `action` takes arguments from the query, the user or the clipboard
+pub fn is_parameterized(action: &str) -> bool {
+    PLACEHOLDERS
+        .iter()
+        .any(|placeholder| action.contains(placeholder))
+}
+
+/// Whether `action` prompts for its argument when none was typed
+pub fn needs_input(action: &str) -> bool {
+    action.contains("{input}")
+}
+
+/// The text typed after a command's name, e.g. `123` for `gh pr` in `gh pr 123`
+pub fn typed_argument<'a>(name: &str, query: &'a str) -> Option<&'a str> {
+    let head = query.get(..name.len())?;
+    if !head.eq_ignore_ascii_case(name) {
+        return None;
+    }
+
+    let argument = query[name.len()..].strip_prefix(' ')?.trim();
+    (!argument.is_empty()).then_some(argument)
+}
+
+/// Shell quotes open where a placeholder appears in an action
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+/// Substitute placeholders in `action`, each value escaped to stay a single
+/// shell word, including inside quotes the action already has
+pub fn expand_action(action: &str, argument: &str, clipboard: &str) -> String {
+    let mut expanded = String::with_capacity(action.len() + argument.len());
+    let mut quoting = Quoting::None;
+    let mut escaped = false;
+    let mut rest = action;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(&placeholder) = PLACEHOLDERS.iter().find(|p| rest.starts_with(*p)) {
+            let value = if placeholder == "{clipboard}" {
+                clipboard
+            } else {
+                argument
+            };
+            expanded.push_str(&quote_in(value, quoting));
+            rest = &rest[placeholder.len()..];
+            continue;
+        }
+
+        if escaped {
+            escaped = false;
+        } else {
+            match (quoting, c) {
+                (Quoting::None | Quoting::Double, '\\') => escaped = true,
+                (Quoting::None, '\'') => quoting = Quoting::Single,
+                (Quoting::None, '"') => quoting = Quoting::Double,
+                (Quoting::Single, '\'') | (Quoting::Double, '"') => quoting = Quoting::None,
+                _ => {}
+            }
+        }
+
+        expanded.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    expanded
+}
+
+fn quote_in(value: &str, quoting: Quoting) -> String {
+    match quoting {
+        Quoting::None => shell_quote(value),
+        Quoting::Single => value.replace('\'', r"'\''"),
+        Quoting::Double => {
+            let mut escaped = String::with_capacity(value.len());
+            for c in value.chars() {
+                if matches!(c, '$' | '`' | '"' | '\\') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+    }
+}
+
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Whether `key` can be assigned in a shell command line as it is
+pub fn is_env_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// What `sh` makes of an expanded action
+    fn run(action: &str, argument: &str) -> String {
+        let command = expand_action(action, argument, "");
+        let output = Command::new("sh").arg("-c").arg(&command).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn shell_quote_keeps_values_literal() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$(reboot)"), "'$(reboot)'");
+    }
+
+    #[test]
+    fn env_names_are_shell_identifiers() {
+        assert!(is_env_name("FOO"));
+        assert!(is_env_name("_foo_2"));
+        assert!(!is_env_name(""));
+        assert!(!is_env_name("2FOO"));
+        assert!(!is_env_name("FOO-BAR"));
+        assert!(!is_env_name("FOO;rm -rf ~"));
+    }
+
+    #[test]
+    fn typed_argument_follows_the_command_name() {
+        assert_eq!(typed_argument("gh pr", "gh pr 123"), Some("123"));
+        assert_eq!(typed_argument("gh pr", "GH PR  123 "), Some("123"));
+        assert_eq!(typed_argument("gh pr", "gh pr"), None);
+        assert_eq!(typed_argument("gh pr", "gh pr "), None);
+        assert_eq!(typed_argument("gh pr", "gh prs 123"), None);
+        assert_eq!(typed_argument("gh pr", "gh"), None);
+    }
+
+    #[test]
+    fn only_input_asks_for_an_argument() {
+        assert!(is_parameterized("gh pr view {query}"));
+        assert!(is_parameterized("pbpaste | say '{clipboard}'"));
+        assert!(!is_parameterized("open ~/{name}"));
+        assert!(needs_input("open {input}"));
+        assert!(!needs_input("open {query}"));
+    }
+
+    #[test]
+    fn clipboard_and_query_expand_separately() {
+        assert_eq!(
+            expand_action("say {query} {clipboard}", "hi", "it's"),
+            r"say 'hi' 'it'\''s'"
+        );
+        assert_eq!(expand_action("open {input}", "", "x"), "open ''");
+        assert_eq!(expand_action("echo {name}", "x", "y"), "echo {name}");
+    }
+
+    #[test]
+    fn placeholders_in_quotes_expand_to_the_literal_value() {
+        for value in ["it's", r#"say "hi""#, "$(echo no) `echo no` \\n", ""] {
+            assert_eq!(run("printf %s {query}", value), value);
+            assert_eq!(run("printf %s '{query}'", value), value);
+            assert_eq!(run("printf %s \"{query}\"", value), value);
+            assert_eq!(
+                run("printf %s \"a '{query}' b\"", value),
+                format!("a '{value}' b")
+            );
+            assert_eq!(
+                run("printf %s 'a \"{query}\" b'", value),
+                format!("a \"{value}\" b")
+            );
+        }
+    }
+}
@@ -0,0 +1,69 @@
+#[cfg(any(target_os = "macos", test))]
+use crate::core::element::Element;
+#[cfg(any(target_os = "macos", test))]
+use crate::core::placeholder::typed_argument;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Quicklink {
+    pub keyword: String,
+    /// URL with `{query}` replaced by the URL-encoded search text
+    pub url: String,
+    /// Shown in the result instead of the keyword
+    pub name: Option<String>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl Quicklink {
+    /// The result offered for `query` when it starts with this keyword
+    pub fn resolve(&self, query: &str) -> Option<Element> {
+        let argument = typed_argument(&self.keyword, query)?;
+        let url = self.url.replace("{query}", &urlencoding::encode(argument));
+        let label = self.name.as_deref().unwrap_or(&self.keyword);
+        Some(Element::new_quicklink(
+            format!("{}: {}", label, argument),
+            url,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::element::ElementType;
+
+    fn quicklink(name: Option<&str>) -> Quicklink {
+        Quicklink {
+            keyword: "gh".into(),
+            url: "https://github.com/search?q={query}".into(),
+            name: name.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn keyword_must_be_followed_by_a_query() {
+        let quicklink = quicklink(None);
+        assert!(quicklink.resolve("gh").is_none());
+        assert!(quicklink.resolve("gh ").is_none());
+        assert!(quicklink.resolve("ghost").is_none());
+        assert!(quicklink.resolve("g rust").is_none());
+        assert!(quicklink.resolve("GH rust").is_some());
+    }
+
+    #[test]
+    fn query_is_url_encoded() {
+        let element = quicklink(None).resolve("gh  a&b c/ü ").unwrap();
+        assert_eq!(
+            &*element.value,
+            "https://github.com/search?q=a%26b%20c%2F%C3%BC"
+        );
+        assert_eq!(&*element.name, "gh: a&b c/ü");
+        assert_eq!(element.element_type, ElementType::Quicklink);
+    }
+
+    #[test]
+    fn name_replaces_the_keyword_in_the_label() {
+        let element = quicklink(Some("GitHub")).resolve("gh frisk").unwrap();
+        assert_eq!(&*element.name, "GitHub: frisk");
+    }
+}
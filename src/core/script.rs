@@ -0,0 +1,214 @@
+#[cfg(target_os = "macos")]
+use crate::core::commands::CommandOptions;
+use crate::core::element::{Element, ElementType};
+use crate::core::error::{Error, Result};
+#[cfg(target_os = "macos")]
+use crate::core::exec::{self, Background};
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "macos")]
+use std::process::{Output, Stdio};
+#[cfg(target_os = "macos")]
+use std::time::Duration;
+
+/// How long a script filter may run when its command sets no `timeout`
+#[cfg(target_os = "macos")]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One line of script filter output, also pushed to the picker over IPC
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScriptItem {
+    pub name: String,
+    /// Defaults to the name
+    pub value: Option<String>,
+    #[serde(rename = "type")]
+    pub element_type: Option<String>,
+    /// Shell command run when the item is selected
+    pub action: Option<String>,
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+impl ScriptItem {
+    pub fn into_element(self) -> Result<Element> {
+        if let Some(action) = self.action {
+            if let Some(name) = &self.element_type {
+                return Err(Error::new(format!(
+                    "An item with an action is a command, so it can't have type {}",
+                    name
+                )));
+            }
+            let mut element = Element::new_system_command(self.name, action);
+            element.confirm = self.confirm;
+            return Ok(element);
+        }
+
+        let element_type = match &self.element_type {
+            Some(name) => ElementType::from_name(name)
+                .ok_or_else(|| Error::new(format!("Unknown type: {}", name)))?,
+            None => ElementType::Text,
+        };
+        let value = self.value.unwrap_or_else(|| self.name.clone());
+
+        Ok(Element {
+            name: self.name.into_boxed_str(),
+            value: value.into_boxed_str(),
+            element_type,
+            confirm: self.confirm,
+        })
+    }
+}
+
+/// Parse JSON lines of name/value/type/action, skipping blank lines
+pub fn parse_items(output: &str) -> Result<Vec<ScriptItem>> {
+    let mut items = Vec::new();
+
+    for (number, line) in output.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(line)
+            .map_err(|e| Error::new(format!("Line {}: {}", number + 1, e)))?;
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// The elements for JSON lines of script filter output
+#[cfg(any(target_os = "macos", test))]
+pub fn parse_lines(output: &str) -> Result<Vec<Element>> {
+    into_elements(parse_items(output)?)
+}
+
+/// The elements for items pushed over IPC. Unlike script filter output, which
+/// has always had the value of an item with an action ignored, a pushed item
+/// can't have both.
+pub fn pushed_elements(items: Vec<ScriptItem>) -> Result<Vec<Element>> {
+    for (number, item) in items.iter().enumerate() {
+        if item.action.is_some() && item.value.is_some() {
+            return Err(Error::new(format!(
+                "Item {}: An item with an action runs it when selected, so it can't have a value",
+                number + 1
+            )));
+        }
+    }
+    into_elements(items)
+}
+
+fn into_elements(items: Vec<ScriptItem>) -> Result<Vec<Element>> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(number, item)| {
+            item.into_element()
+                .map_err(|e| Error::new(format!("Item {}: {}", number + 1, e)))
+        })
+        .collect()
+}
+
+/// Start a script filter in the background, killing it after its `timeout`
+#[cfg(target_os = "macos")]
+pub fn spawn(command: &str, options: &CommandOptions) -> Result<Background> {
+    let child = exec::build(command, options)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::new(format!("Failed to run script filter: {}", e)))?;
+
+    let timeout = options.timeout.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+    Ok(Background::wait(child, Some(timeout)))
+}
+
+/// Parse what a finished script filter printed
+#[cfg(target_os = "macos")]
+pub fn finish(result: Result<Output>) -> Result<Vec<Element>> {
+    let output = result.map_err(|e| Error::new(format!("Script filter failed: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::new(format!(
+            "Script filter failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_lines(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(output: &str) -> String {
+        parse_lines(output).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn lines_become_elements() {
+        let output = concat!(
+            r#"{"name": "main"}"#,
+            "\n\n",
+            r#"{"name": "Docs", "value": "https://docs.rs", "type": "quicklink"}"#,
+            "\n",
+            r#"{"name": "Deploy", "action": "make deploy", "confirm": true}"#,
+        );
+        let elements = parse_lines(output).unwrap();
+
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0].value.as_ref(), "main");
+        assert_eq!(elements[0].element_type, ElementType::Text);
+        assert_eq!(elements[1].element_type, ElementType::Quicklink);
+        assert_eq!(elements[2].value.as_ref(), "make deploy");
+        assert_eq!(elements[2].element_type, ElementType::SystemCommand);
+        assert!(elements[2].confirm);
+    }
+
+    #[test]
+    fn bad_json_names_the_line() {
+        let error = error("{\"name\": \"ok\"}\n\nnot json");
+        assert!(error.starts_with("Line 3: "), "{error}");
+    }
+
+    #[test]
+    fn name_is_required() {
+        let error = error(r#"{"value": "x"}"#);
+        assert!(error.starts_with("Line 1: missing field `name`"), "{error}");
+    }
+
+    #[test]
+    fn action_and_type_conflict() {
+        let error = error(r#"{"name": "a", "type": "text", "action": "true"}"#);
+        assert_eq!(
+            error,
+            "Item 1: An item with an action is a command, so it can't have type text"
+        );
+    }
+
+    #[test]
+    fn action_ignores_the_value_in_script_output() {
+        let elements = parse_lines(r#"{"name": "a", "value": "b", "action": "true"}"#).unwrap();
+        assert_eq!(elements[0].value.as_ref(), "true");
+    }
+
+    #[test]
+    fn pushed_action_and_value_conflict() {
+        let items = parse_items(concat!(
+            r#"{"name": "a"}"#,
+            "\n",
+            r#"{"name": "b", "value": "c", "action": "true"}"#,
+        ))
+        .unwrap();
+        let error = pushed_elements(items).err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "Item 2: An item with an action runs it when selected, so it can't have a value"
+        );
+    }
+
+    #[test]
+    fn unknown_type_names_the_item() {
+        let error = error("{\"name\": \"a\"}\n{\"name\": \"b\", \"type\": \"widget\"}");
+        assert_eq!(error, "Item 2: Unknown type: widget");
+    }
+}
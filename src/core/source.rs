@@ -0,0 +1,394 @@
+use crate::core::element::ElementType;
+use crate::core::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    /// A frisk cache file of encoded elements
+    #[default]
+    Bincode,
+    /// A commands.toml style list of `[[command]]` entries
+    Commands,
+    /// A text file with one item per line
+    Lines,
+}
+
+/// Score adjustment and result cap for a source or element type
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ranking {
+    pub multiplier: f64,
+    /// Added to the score after the multiplier
+    pub bias: i32,
+    /// Most results shown at once
+    pub max_results: Option<usize>,
+}
+
+impl Default for Ranking {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            bias: 0,
+            max_results: None,
+        }
+    }
+}
+
+impl Ranking {
+    pub fn apply(&self, score: u32) -> u32 {
+        (score as f64 * self.multiplier + self.bias as f64).clamp(0.0, u32::MAX as f64) as u32
+    }
+}
+
+/// A `[[source]]` table. Entries named after a built-in source override
+/// only the fields they set.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SourceConfig {
+    pub name: String,
+    /// File to load. Relative paths of every format are in the cache dir, and
+    /// bincode sources without a path default to `<name>.bin` there.
+    pub path: Option<String>,
+    pub format: Option<SourceFormat>,
+    /// Load this source when no sources are given on the command line
+    pub enabled: Option<bool>,
+    /// Sources with a higher priority are loaded first
+    pub priority: Option<i32>,
+    /// Shown when the source's file is missing
+    pub hint: Option<String>,
+    pub multiplier: Option<f64>,
+    pub bias: Option<i32>,
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub name: String,
+    pub path: Option<String>,
+    pub format: SourceFormat,
+    pub enabled: bool,
+    pub priority: i32,
+    pub hint: Option<String>,
+    pub ranking: Ranking,
+}
+
+impl Source {
+    fn builtin(name: &str, format: SourceFormat, priority: i32, hint: Option<&str>) -> Self {
+        Self {
+            name: name.into(),
+            path: None,
+            format,
+            enabled: false,
+            priority,
+            hint: hint.map(Into::into),
+            ranking: Ranking::default(),
+        }
+    }
+
+    /// A `[[source]]` table setting every field
+    pub fn to_config(&self) -> SourceConfig {
+        SourceConfig {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            format: Some(self.format),
+            enabled: Some(self.enabled),
+            priority: Some(self.priority),
+            hint: self.hint.clone(),
+            multiplier: Some(self.ranking.multiplier),
+            bias: Some(self.ranking.bias),
+            max_results: self.ranking.max_results,
+        }
+    }
+
+    fn apply(&mut self, config: SourceConfig) {
+        if config.path.is_some() {
+            self.path = config.path;
+        }
+        if config.hint.is_some() {
+            self.hint = config.hint;
+        }
+        self.format = config.format.unwrap_or(self.format);
+        self.enabled = config.enabled.unwrap_or(self.enabled);
+        self.priority = config.priority.unwrap_or(self.priority);
+        if let Some(multiplier) = config.multiplier {
+            self.ranking.multiplier = multiplier;
+        }
+        if let Some(bias) = config.bias {
+            self.ranking.bias = bias;
+        }
+        if config.max_results.is_some() {
+            self.ranking.max_results = config.max_results;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SourcesFile {
+    #[serde(default)]
+    source: Vec<SourceConfig>,
+    #[serde(default)]
+    ranking: BTreeMap<String, Ranking>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceRegistry {
+    sources: Vec<Source>,
+    type_ranking: Vec<(ElementType, Ranking)>,
+}
+
+impl SourceRegistry {
+    /// Built-in sources, overridden or extended by `[[source]]` entries, plus
+    /// `[ranking]` tables keyed by element type
+    pub fn new(configured: Vec<SourceConfig>, ranking: &BTreeMap<String, Ranking>) -> Result<Self> {
+        let mut sources = vec![
+            Source::builtin(
+                "apps",
+                SourceFormat::Bincode,
+                60,
+                Some("frisk service install apps && frisk service start apps"),
+            ),
+            Source::builtin(
+                "homebrew",
+                SourceFormat::Bincode,
+                50,
+                Some("frisk service install homebrew && frisk service start homebrew"),
+            ),
+            Source::builtin(
+                "clipboard",
+                SourceFormat::Bincode,
+                40,
+                Some("frisk service install clipboard && frisk service start clipboard"),
+            ),
+            Source::builtin("commands", SourceFormat::Commands, 30, None),
+            Source::builtin(
+                "nixpkgs",
+                SourceFormat::Bincode,
+                20,
+                Some("frisk daemon nixpkgs"),
+            ),
+            Source::builtin(
+                "dictionary",
+                SourceFormat::Bincode,
+                10,
+                Some("frisk daemon dictionary"),
+            ),
+        ];
+
+        for config in configured {
+            match sources.iter_mut().find(|s| s.name == config.name) {
+                Some(existing) => existing.apply(config),
+                None => {
+                    let mut source =
+                        Source::builtin(&config.name, SourceFormat::default(), 0, None);
+                    source.apply(config);
+                    sources.push(source);
+                }
+            }
+        }
+
+        let mut type_ranking = Vec::with_capacity(ranking.len());
+        for (name, ranking) in ranking {
+            let element_type = ElementType::from_name(name)
+                .ok_or_else(|| Error::new(format!("Unknown ranking type: {}", name)))?;
+            type_ranking.push((element_type, *ranking));
+        }
+
+        Ok(Self {
+            sources,
+            type_ranking,
+        })
+    }
+
+    /// Read only the `[[source]]` tables of a config file, for commands that
+    /// don't need the rest of the config
+    pub fn load(config_path: Option<PathBuf>) -> Result<Self> {
+        let path = crate::core::config::config_path(config_path)?;
+        if !path.exists() {
+            return Self::new(Vec::new(), &BTreeMap::new());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let file: SourcesFile = toml::from_str(&content)?;
+        Self::new(file.source, &file.ranking)
+    }
+
+    pub fn type_ranking(&self) -> &[(ElementType, Ranking)] {
+        &self.type_ranking
+    }
+
+    pub fn all(&self) -> &[Source] {
+        &self.sources
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Source> {
+        self.sources.iter().find(|s| s.name == name)
+    }
+
+    /// The sources to load, highest priority first. Falls back to the sources
+    /// enabled by default when nothing was requested.
+    pub fn select(&self, names: &[String], use_defaults: bool) -> Vec<&Source> {
+        let mut selected: Vec<&Source> = Vec::new();
+
+        if use_defaults && names.is_empty() {
+            selected.extend(self.sources.iter().filter(|s| s.enabled));
+        }
+
+        for name in names {
+            if selected.iter().any(|s| s.name == *name) {
+                continue;
+            }
+            match self.get(name) {
+                Some(source) => selected.push(source),
+                None => eprintln!("Warning: unknown source: {}", name),
+            }
+        }
+
+        selected.sort_by_key(|s| std::cmp::Reverse(s.priority));
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(config: &str) -> SourceRegistry {
+        let file: SourcesFile = toml::from_str(config).unwrap();
+        SourceRegistry::new(file.source, &file.ranking).unwrap()
+    }
+
+    fn names(sources: &[&Source]) -> Vec<String> {
+        sources.iter().map(|s| s.name.clone()).collect()
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn ranking_scales_then_biases() {
+        let ranking = Ranking {
+            multiplier: 1.5,
+            bias: -20,
+            max_results: None,
+        };
+        assert_eq!(ranking.apply(100), 130);
+        assert_eq!(ranking.apply(10), 0);
+        assert_eq!(Ranking::default().apply(42), 42);
+    }
+
+    #[test]
+    fn builtin_override_keeps_unset_fields() {
+        let registry = registry(
+            r#"
+            [[source]]
+            name = "apps"
+            enabled = true
+            multiplier = 2.0
+            "#,
+        );
+        let apps = registry.get("apps").unwrap();
+
+        assert!(apps.enabled);
+        assert_eq!(apps.priority, 60);
+        assert_eq!(apps.format, SourceFormat::Bincode);
+        assert!(apps.hint.is_some());
+        assert_eq!(apps.ranking.multiplier, 2.0);
+        assert_eq!(apps.ranking.bias, 0);
+        assert_eq!(registry.all().len(), 6);
+    }
+
+    #[test]
+    fn new_sources_are_added_with_defaults() {
+        let registry = registry(
+            r#"
+            [[source]]
+            name = "bookmarks"
+            path = "bookmarks.txt"
+            format = "lines"
+            "#,
+        );
+        let bookmarks = registry.get("bookmarks").unwrap();
+
+        assert_eq!(bookmarks.path.as_deref(), Some("bookmarks.txt"));
+        assert_eq!(bookmarks.format, SourceFormat::Lines);
+        assert!(!bookmarks.enabled);
+        assert_eq!(bookmarks.priority, 0);
+        assert_eq!(bookmarks.ranking, Ranking::default());
+    }
+
+    #[test]
+    fn defaults_apply_only_without_names() {
+        let registry = registry(
+            r#"
+            [[source]]
+            name = "apps"
+            enabled = true
+
+            [[source]]
+            name = "commands"
+            enabled = true
+            "#,
+        );
+
+        assert_eq!(names(&registry.select(&[], true)), ["apps", "commands"]);
+        assert!(registry.select(&[], false).is_empty());
+        assert_eq!(
+            names(&registry.select(&strings(&["nixpkgs"]), true)),
+            ["nixpkgs"]
+        );
+    }
+
+    #[test]
+    fn selection_is_by_priority_without_duplicates() {
+        let registry = registry(
+            r#"
+            [[source]]
+            name = "bookmarks"
+            priority = 100
+            "#,
+        );
+        let selected =
+            registry.select(&strings(&["dictionary", "apps", "bookmarks", "apps"]), true);
+
+        assert_eq!(names(&selected), ["bookmarks", "apps", "dictionary"]);
+    }
+
+    #[test]
+    fn unknown_names_are_skipped() {
+        let registry = registry("");
+        let selected = registry.select(&strings(&["apps", "nope"]), true);
+
+        assert_eq!(names(&selected), ["apps"]);
+    }
+
+    #[test]
+    fn ranking_is_keyed_by_element_type() {
+        let registry = registry(
+            r#"
+            [ranking.apps]
+            bias = 10
+            "#,
+        );
+        assert_eq!(
+            registry.type_ranking(),
+            [(
+                ElementType::Application,
+                Ranking {
+                    bias: 10,
+                    ..Ranking::default()
+                }
+            )]
+        );
+
+        let file: SourcesFile = toml::from_str("[ranking.widgets]\nbias = 1").unwrap();
+        let error = SourceRegistry::new(file.source, &file.ranking)
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(error, "Unknown ranking type: widgets");
+    }
+}
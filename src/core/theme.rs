@@ -0,0 +1,269 @@
+use crate::core::color::Color;
+use crate::core::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BUILTIN_THEMES: [(&str, &str); 6] = [
+    ("onedark", include_str!("themes/onedark.toml")),
+    ("onelight", include_str!("themes/onelight.toml")),
+    ("gruvbox-dark", include_str!("themes/gruvbox-dark.toml")),
+    ("gruvbox-light", include_str!("themes/gruvbox-light.toml")),
+    ("nord", include_str!("themes/nord.toml")),
+    ("dracula", include_str!("themes/dracula.toml")),
+];
+
+/// The keys of `[styles]`, and all a theme file may set
+pub const STYLE_KEYS: [&str; 7] = [
+    "background",
+    "items",
+    "selected_item",
+    "prompt",
+    "query",
+    "caret",
+    "match",
+];
+
+/// Picker colours. Each colour left unset falls through to the next layer:
+/// the flat keys, `[styles]`, the theme and then the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Styles {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_item: Option<String>,
+    /// Defaults to the query colour
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caret: Option<String>,
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    pub match_text: Option<String>,
+}
+
+impl Styles {
+    pub fn defaults() -> Self {
+        Self {
+            background: Some("#282c34".into()),
+            items: Some("#ffffff".into()),
+            selected_item: Some("#61afef".into()),
+            prompt: None,
+            query: Some("#e06c75".into()),
+            caret: Some("#e06c75".into()),
+            match_text: Some("#e5c07b".into()),
+        }
+    }
+
+    /// These styles, with unset colours taken from `below`
+    pub fn or(self, below: Styles) -> Self {
+        Self {
+            background: self.background.or(below.background),
+            items: self.items.or(below.items),
+            selected_item: self.selected_item.or(below.selected_item),
+            prompt: self.prompt.or(below.prompt),
+            query: self.query.or(below.query),
+            caret: self.caret.or(below.caret),
+            match_text: self.match_text.or(below.match_text),
+        }
+    }
+}
+
+/// `theme = "name"`, or a theme for each system appearance
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ThemeSetting {
+    Name(String),
+    Appearance { light: String, dark: String },
+}
+
+impl ThemeSetting {
+    /// The theme to use now. This is only asked when the config loads, so a
+    /// picker that stays open keeps its theme until the config reloads.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Name(name) => name,
+            Self::Appearance { light, dark } => {
+                if dark_mode() {
+                    dark
+                } else {
+                    light
+                }
+            }
+        }
+    }
+
+    /// Every theme this setting names
+    #[cfg(any(target_os = "macos", test))]
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Name(name) => vec![name],
+            Self::Appearance { light, dark } => vec![light, dark],
+        }
+    }
+}
+
+/// Themes live next to config.toml in `themes/<name>.toml`
+pub fn themes_dir(config_path: &Path) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("themes")
+}
+
+pub fn theme_path(themes_dir: &Path, name: &str) -> PathBuf {
+    themes_dir.join(format!("{}.toml", name))
+}
+
+/// Whether a theme file or built-in theme called `name` exists
+pub fn exists(themes_dir: &Path, name: &str) -> bool {
+    theme_path(themes_dir, name).exists() || builtin(name).is_some()
+}
+
+/// Load a theme from the themes directory, or a built-in one of that name.
+/// Unlike `[styles]`, a theme can only set colours, so unknown keys and
+/// invalid colours are errors.
+pub fn load(themes_dir: &Path, name: &str) -> Result<Styles> {
+    let path = theme_path(themes_dir, name);
+    let content = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        builtin(name)
+            .ok_or_else(|| Error::new(format!("Unknown theme: {}", name)))?
+            .to_string()
+    };
+
+    parse(&content).map_err(|e| Error::new(format!("Theme {}: {}", name, e)))
+}
+
+fn parse(content: &str) -> Result<Styles> {
+    let table: toml::Table = toml::from_str(content)?;
+    for (key, value) in &table {
+        if !STYLE_KEYS.contains(&key.as_str()) {
+            return Err(Error::new(format!("unknown key `{}`", key)));
+        }
+        if let Some(color) = value.as_str() {
+            Color::parse(color)?;
+        }
+    }
+    Ok(table.try_into()?)
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN_THEMES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, content)| *content)
+}
+
+/// macOS only sets AppleInterfaceStyle while dark mode is on
+fn dark_mode() -> bool {
+    Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "Dark")
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn builtin_themes_set_every_colour() {
+        for (name, _) in BUILTIN_THEMES {
+            let styles = load(Path::new("/nonexistent"), name).unwrap();
+            for color in [
+                &styles.background,
+                &styles.items,
+                &styles.selected_item,
+                &styles.prompt,
+                &styles.query,
+                &styles.caret,
+                &styles.match_text,
+            ] {
+                let color = color
+                    .as_deref()
+                    .unwrap_or_else(|| panic!("{} is incomplete", name));
+                Color::parse(color).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn or_fills_only_unset_colours() {
+        let top = Styles {
+            items: Some("red".into()),
+            ..Styles::default()
+        };
+        let middle = Styles {
+            items: Some("blue".into()),
+            caret: Some("green".into()),
+            ..Styles::default()
+        };
+        let styles = top.or(middle).or(Styles::defaults());
+        assert_eq!(styles.items.as_deref(), Some("red"));
+        assert_eq!(styles.caret.as_deref(), Some("green"));
+        assert_eq!(styles.background, Styles::defaults().background);
+        assert_eq!(styles.prompt, None);
+    }
+
+    #[test]
+    fn setting_is_a_name_or_one_per_appearance() {
+        #[derive(Deserialize)]
+        struct Config {
+            theme: ThemeSetting,
+        }
+        let theme = |content: &str| toml::from_str::<Config>(content).map(|config| config.theme);
+
+        assert_eq!(
+            theme("theme = \"nord\"").unwrap(),
+            ThemeSetting::Name("nord".into())
+        );
+        let appearance = theme("theme = { light = \"onelight\", dark = \"onedark\" }").unwrap();
+        assert_eq!(
+            appearance,
+            ThemeSetting::Appearance {
+                light: "onelight".into(),
+                dark: "onedark".into()
+            }
+        );
+        assert_eq!(appearance.names(), ["onelight", "onedark"]);
+        assert!(theme("theme = { light = \"onelight\" }").is_err());
+    }
+
+    #[test]
+    fn theme_files_take_the_place_of_builtins() {
+        let dir = TempDir::new().unwrap();
+        fs::write(theme_path(dir.path(), "nord"), "items = \"#123456\"\n").unwrap();
+
+        let styles = load(dir.path(), "nord").unwrap();
+        assert_eq!(styles.items.as_deref(), Some("#123456"));
+        assert_eq!(styles.background, None);
+        assert!(exists(dir.path(), "nord"));
+        assert!(!exists(dir.path(), "mine"));
+        assert!(load(dir.path(), "mine").is_err());
+    }
+
+    #[test]
+    fn theme_files_only_set_valid_colours() {
+        let dir = TempDir::new().unwrap();
+        fs::write(theme_path(dir.path(), "typo"), "itmes = \"#fff\"\n").unwrap();
+        fs::write(theme_path(dir.path(), "bad"), "items = \"#ggg\"\n").unwrap();
+
+        let error = load(dir.path(), "typo").unwrap_err().to_string();
+        assert_eq!(error, "Theme typo: unknown key `itmes`");
+        let error = load(dir.path(), "bad").unwrap_err().to_string();
+        assert!(
+            error.starts_with("Theme bad: invalid colour `#ggg`"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::core::error::Result;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+
+/// Watches a set of files for changes. Their directories are watched rather
+/// than the files themselves, as editors often save by replacing the file,
+/// and symlinks are followed so dotfile checkouts are watched where they live.
+/// Files that don't exist yet are reported once they are created.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+    files: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new(files: Vec<PathBuf>) -> Result<Self> {
+        let (tx, rx) = channel();
+
+        let mut watcher = RecommendedWatcher::new(
+            move |res: notify::Result<Event>| {
+                let _ = tx.send(res);
+            },
+            notify::Config::default(),
+        )?;
+
+        let files: Vec<PathBuf> = files.into_iter().map(resolve).collect();
+
+        let mut dirs: Vec<PathBuf> = files
+            .iter()
+            .filter_map(|file| file.parent().map(PathBuf::from))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+
+        for dir in &dirs {
+            if !dir.exists() {
+                crate::log!("Not watching {:?}, it doesn't exist", dir);
+                continue;
+            }
+            match watcher.watch(dir, RecursiveMode::NonRecursive) {
+                Ok(_) => crate::log!("Watching {:?}", dir),
+                Err(e) => crate::log!("Failed to watch {:?}: {}", dir, e),
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+            files,
+        })
+    }
+
+    /// The watched files changed since the last call
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+
+        while let Ok(res) = self.rx.try_recv() {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    crate::log!("Watch error: {:?}", e);
+                    continue;
+                }
+            };
+
+            if !matches!(
+                event.kind,
+                notify::EventKind::Create(_)
+                    | notify::EventKind::Remove(_)
+                    | notify::EventKind::Modify(_)
+            ) {
+                continue;
+            }
+
+            for path in event.paths {
+                if self.files.contains(&path) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+/// Where a file lives, or would once created, with symlinks resolved so it
+/// compares equal to the paths of events in its directory
+fn resolve(file: PathBuf) -> PathBuf {
+    if let Ok(file) = fs::canonicalize(&file) {
+        return file;
+    }
+    match (file.parent(), file.file_name()) {
+        (Some(dir), Some(name)) => fs::canonicalize(dir)
+            .map(|dir| dir.join(name))
+            .unwrap_or(file),
+        _ => file,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    /// Changes reported within a second
+    fn wait_for_changes(watcher: &FileWatcher) -> Vec<PathBuf> {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut changed = Vec::new();
+        while Instant::now() < deadline {
+            for path in watcher.changed() {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        changed
+    }
+
+    #[test]
+    fn file_created_later_through_a_symlink_is_reported() {
+        let dir = TempDir::new().unwrap();
+        let links = TempDir::new().unwrap();
+        let link = links.path().join("frisk");
+        std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+
+        let watcher = FileWatcher::new(vec![link.join("commands.toml")]).unwrap();
+        fs::write(dir.path().join("other.toml"), "").unwrap();
+        fs::write(dir.path().join("commands.toml"), "").unwrap();
+
+        // FSEvents reports where the file lives, not the path it was watched by
+        let file = fs::canonicalize(dir.path()).unwrap().join("commands.toml");
+        assert_eq!(resolve(link.join("commands.toml")), file);
+        assert_eq!(wait_for_changes(&watcher), [file]);
+    }
+
+    #[test]
+    fn file_replaced_by_a_rename_is_reported() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("config.toml");
+        fs::write(&file, "").unwrap();
+
+        let watcher = FileWatcher::new(vec![file.clone()]).unwrap();
+        let saved = dir.path().join("config.toml.tmp");
+        fs::write(&saved, "prompt = \"> \"").unwrap();
+        fs::rename(&saved, &file).unwrap();
+
+        assert_eq!(
+            wait_for_changes(&watcher),
+            [fs::canonicalize(&file).unwrap()]
+        );
+    }
+
+    #[test]
+    fn files_in_missing_directories_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("missing").join("commands.toml");
+
+        let watcher = FileWatcher::new(vec![file]).unwrap();
+        assert!(watcher.changed().is_empty());
+    }
+}
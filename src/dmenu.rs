@@ -0,0 +1,101 @@
+use std::io::{self, BufRead, Write};
+
+use crate::cli::Cli;
+use crate::core::element::{Element, ElementList};
+#[cfg(not(target_os = "macos"))]
+use crate::core::error::Error;
+use crate::core::error::Result;
+#[cfg(target_os = "macos")]
+use crate::gui;
+#[cfg(target_os = "macos")]
+use crate::picker::{self, PickerMode};
+
+pub fn run(cli: Cli) -> Result<()> {
+    let mut elements = read_elements(io::stdin().lock())?;
+    crate::log!("Read {} items from stdin", elements.len());
+
+    if let Some(query) = &cli.filter {
+        let matched = filter(&mut elements, query, &mut io::stdout().lock())?;
+        if matched == 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    show(cli, elements)
+}
+
+#[cfg(target_os = "macos")]
+fn show(cli: Cli, elements: ElementList) -> Result<()> {
+    let config = gui::load_config(&cli)?;
+    picker::run(config, elements, None, PickerMode::Dmenu)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn show(_cli: Cli, _elements: ElementList) -> Result<()> {
+    Err(Error::new(
+        "The picker only runs on macOS, use --filter to match without it",
+    ))
+}
+
+fn read_elements(reader: impl BufRead) -> Result<ElementList> {
+    let mut elements = ElementList::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        elements.add(Element::new_text(line));
+    }
+
+    Ok(elements)
+}
+
+fn filter(elements: &mut ElementList, query: &str, out: &mut impl Write) -> Result<usize> {
+    let matches = elements.search(query);
+
+    for m in &matches {
+        writeln!(out, "{}", elements.inner[m.index].value)?;
+    }
+    out.flush()?;
+
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(elements: &ElementList) -> Vec<&str> {
+        elements.inner.iter().map(|e| e.name.as_ref()).collect()
+    }
+
+    fn filtered(input: &str, query: &str) -> (usize, String) {
+        let mut elements = read_elements(input.as_bytes()).unwrap();
+        let mut out = Vec::new();
+        let matched = filter(&mut elements, query, &mut out).unwrap();
+        (matched, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn lines_become_text_items() {
+        let elements = read_elements("main\n\n  \nfeature/search \n".as_bytes()).unwrap();
+        assert_eq!(names(&elements), ["main", "feature/search "]);
+        assert_eq!(elements.inner[1].value.as_ref(), "feature/search ");
+    }
+
+    #[test]
+    fn filter_prints_the_values_that_match() {
+        let (matched, out) = filtered("config.toml\nREADME.md\nconf.d\n", "conf");
+        assert_eq!(matched, 2);
+        let mut lines: Vec<&str> = out.lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["conf.d", "config.toml"]);
+    }
+
+    #[test]
+    fn filter_without_matches_prints_nothing() {
+        assert_eq!(filtered("main\ndev\n", "xyz"), (0, String::new()));
+    }
+}
@@ -1,64 +1,174 @@
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::cli::Cli;
-use crate::core::error::Result;
+use crate::core::error::{Error, Result};
 use crate::ipc;
 
-static LOCK_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+/// How long an instance that has just taken the lock may take to listen
+const STARTUP_GRACE: Duration = Duration::from_secs(1);
+
+/// The lock file, held for as long as this process is the running picker.
+/// The OS releases it however the process exits, so it can't go stale.
+static LOCK_FILE: Mutex<Option<File>> = Mutex::new(None);
 
 pub fn check_single_instance(cli: &Cli) -> Result<bool> {
-    let lock_file = get_lock_file_path()?;
-
-    if lock_file.exists() {
-        if let Ok(pid_str) = fs::read_to_string(&lock_file) {
-            if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                let status = std::process::Command::new("kill")
-                    .arg("-0")
-                    .arg(pid.to_string())
-                    .stderr(std::process::Stdio::null())
-                    .status();
-
-                if status.is_ok() && status.unwrap().success() {
-                    // Another instance is running - send IPC message
-                    let msg = ipc::IpcMessage::Reload {
-                        apps: cli.apps,
-                        homebrew: cli.homebrew,
-                        clipboard: cli.clipboard,
-                        commands: cli.commands,
-                        nixpkgs: cli.nixpkgs,
-                        dictionary: cli.dictionary,
-                        sources: cli.source.iter().map(|p| p.display().to_string()).collect(),
-                        prompt: cli.prompt.clone(),
-                    };
-
-                    if ipc::send_message(&msg).is_ok() {
-                        return Ok(true);
-                    }
-                    // If socket doesn't exist remove stale lock and continue
-                    let _ = fs::remove_file(&lock_file);
-                }
-            }
-        }
-        let _ = fs::remove_file(&lock_file);
+    let lock_path = get_lock_file_path()?;
+
+    if let Some(lock) = try_lock(&lock_path)? {
+        *LOCK_FILE.lock().unwrap() = Some(lock);
+        return Ok(false);
     }
 
-    let pid = std::process::id();
-    fs::write(&lock_file, pid.to_string())?;
+    // Another instance holds the lock, but it may be hung
+    if let Err(e) = ping_running() {
+        let pid = fs::read_to_string(&lock_path).unwrap_or_default();
+        return Err(Error::new(format!(
+            "frisk (pid {}) is running but isn't answering: {}",
+            pid.trim(),
+            e
+        )));
+    }
 
-    *LOCK_FILE.lock().unwrap() = Some(lock_file);
+    let msg = ipc::IpcMessage::Reload {
+        sources: cli.sources.names(),
+        paths: cli
+            .sources
+            .source
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect(),
+        prompt: cli.prompt.clone(),
+    };
+    ipc::send_message(&msg)?;
+    Ok(true)
+}
 
-    Ok(false)
+/// Remove the socket and release the lock if this process is the running
+/// picker. An invocation that only forwarded a reload leaves both alone.
+pub fn cleanup() {
+    release(&mut LOCK_FILE.lock().unwrap(), ipc::server::cleanup);
 }
 
-pub fn cleanup_lock_file() {
-    if let Some(path) = LOCK_FILE.lock().unwrap().take() {
-        let _ = fs::remove_file(path);
+/// Remove the socket before dropping the lock, so the next instance can't bind
+/// its own first. Does nothing without the lock.
+fn release(lock: &mut Option<File>, remove_socket: impl FnOnce()) {
+    if let Some(file) = lock.take() {
+        remove_socket();
+        // The file stays, so everyone keeps locking the same one
+        drop(file);
+    }
+}
+
+/// Take the lock without waiting and write our pid into the file for people
+/// to read. None if another process holds it.
+fn try_lock(path: &Path) -> Result<Option<File>> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(err.into());
+    }
+
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(Some(file))
+}
+
+/// Ping the instance holding the lock, giving one that has only just started
+/// time to bind its socket
+fn ping_running() -> Result<()> {
+    let start = Instant::now();
+    loop {
+        match ipc::ping() {
+            Ok(_) => return Ok(()),
+            Err(e) if start.elapsed() >= STARTUP_GRACE => return Err(e),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
     }
 }
 
 fn get_lock_file_path() -> Result<PathBuf> {
-    let runtime_dir = PathBuf::from("/tmp");
-    Ok(runtime_dir.join("frisk.lock"))
+    Ok(ipc::runtime_dir()?.join("frisk.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("frisk-test-{}-{}.lock", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let path = lock_path("exclusive");
+
+        let first = try_lock(&path).unwrap();
+        assert!(first.is_some());
+        assert!(try_lock(&path).unwrap().is_none());
+
+        drop(first);
+        assert!(try_lock(&path).unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn only_the_lock_holder_removes_the_socket() {
+        let path = lock_path("cleanup");
+        let socket = path.with_extension("sock");
+        fs::write(&socket, "").unwrap();
+
+        // Forwarded to the running picker, so never took the lock
+        release(&mut None, || fs::remove_file(&socket).unwrap());
+        assert!(socket.exists());
+
+        let mut lock = try_lock(&path).unwrap();
+        release(&mut lock, || fs::remove_file(&socket).unwrap());
+        assert!(!socket.exists());
+        assert!(try_lock(&path).unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn lock_file_holds_our_pid() {
+        let path = lock_path("pid");
+
+        let _lock = try_lock(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn leftover_lock_file_is_taken_over() {
+        // A file left by an instance that was killed, naming a pid that may
+        // since have been reused
+        let path = lock_path("leftover");
+        fs::write(&path, "4294967295").unwrap();
+
+        assert!(try_lock(&path).unwrap().is_some());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,309 @@
+use crate::cli::IpcCommands;
+use crate::core::error::{Error, Result};
+use crate::core::script::ScriptItem;
+use crate::query::{self, QueryResult};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The running picker's end of the socket, only built for its tests off macOS
+#[cfg(any(target_os = "macos", test))]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub mod server;
+
+/// Bumped when requests or replies change shape
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long to wait for the picker to answer. The picker handles requests on
+/// its main thread, so no answer means it's hung.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long requests wait for a picker that is still loading its elements
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IpcMessage {
+    Reload {
+        /// Source names from the registry
+        sources: Vec<String>,
+        /// Additional binary source files
+        paths: Vec<String>,
+        prompt: Option<String>,
+    },
+    /// Ranked results from the elements the picker has loaded
+    Search {
+        query: String,
+        source: SearchSource,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    Ping,
+    /// Items added to the picker's elements without reloading
+    Push {
+        items: Vec<ScriptItem>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum SearchSource {
+    All,
+    Nixpkgs,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    version: u32,
+    id: u64,
+    message: IpcMessage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    version: u32,
+    /// The id of the request this answers, 0 if it couldn't be read
+    id: u64,
+    reply: Reply,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Reply {
+    Ok,
+    Pong,
+    Results(Vec<QueryResult>),
+    Error(String),
+}
+
+/// A directory only the current user can use, for the socket and lock file.
+/// It's in `$XDG_RUNTIME_DIR` or `$TMPDIR`, falling back to /tmp.
+pub fn runtime_dir() -> Result<PathBuf> {
+    let base = ["XDG_RUNTIME_DIR", "TMPDIR"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .find(|dir| dir.is_absolute())
+        .unwrap_or_else(|| PathBuf::from("/tmp"));
+    runtime_dir_in(&base, unsafe { libc::getuid() })
+}
+
+/// `frisk-<uid>` in `base`, created or tightened so only `uid` can use it
+fn runtime_dir_in(base: &Path, uid: libc::uid_t) -> Result<PathBuf> {
+    let dir = base.join(format!("frisk-{}", uid));
+
+    if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&dir) {
+        if e.kind() != io::ErrorKind::AlreadyExists {
+            return Err(Error::new(format!(
+                "Failed to create {}: {}",
+                dir.display(),
+                e
+            )));
+        }
+    }
+
+    // Someone else may have created it first to get at the socket
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(Error::new(format!(
+            "{} isn't a directory owned by you",
+            dir.display()
+        )));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(dir)
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join("frisk.sock"))
+}
+
+pub fn send_message(msg: &IpcMessage) -> Result<Reply> {
+    send_message_to_socket(&socket_path()?, msg)
+}
+
+/// Send a message and wait for the reply, turning error replies into errors
+fn send_message_to_socket(socket_path: &PathBuf, msg: &IpcMessage) -> Result<Reply> {
+    if !socket_path.exists() {
+        return Err(Error::new("Socket doesn't exist"));
+    }
+
+    let mut stream = UnixStream::connect(socket_path)
+        .map_err(|e| Error::new(format!("Failed to connect to IPC socket: {}", e)))?;
+    // Long enough for the listener to report a slow or hung picker itself
+    stream.set_read_timeout(Some(
+        STARTUP_TIMEOUT + REPLY_TIMEOUT + Duration::from_secs(1),
+    ))?;
+
+    let request = Request {
+        version: PROTOCOL_VERSION,
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        message: msg.clone(),
+    };
+    writeln!(stream, "{}", serde_json::to_string(&request)?)?;
+    stream.flush()?;
+
+    crate::log!("Sent IPC message: {:?}", msg);
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                Error::new("The running picker isn't listening")
+            }
+            _ => Error::new(format!("No reply from the running picker: {}", e)),
+        })?;
+    if line.is_empty() {
+        return Err(Error::new("The running picker closed the connection"));
+    }
+
+    let response: Response = serde_json::from_str(&line)?;
+    if response.version != PROTOCOL_VERSION {
+        return Err(Error::new(format!(
+            "The running picker speaks protocol version {}, expected {}",
+            response.version, PROTOCOL_VERSION
+        )));
+    }
+    if response.id != request.id {
+        return Err(Error::new(format!(
+            "Reply to request {} instead of {}",
+            response.id, request.id
+        )));
+    }
+
+    match response.reply {
+        Reply::Error(e) => Err(Error::new(e)),
+        reply => Ok(reply),
+    }
+}
+
+/// Whether a picker is listening and its main thread answers
+pub fn ping() -> Result<Duration> {
+    let start = Instant::now();
+    send_message(&IpcMessage::Ping)?;
+    Ok(start.elapsed())
+}
+
+/// Search the running picker's elements
+pub fn search(
+    query: String,
+    source: SearchSource,
+    limit: Option<usize>,
+) -> Result<Vec<QueryResult>> {
+    match send_message(&IpcMessage::Search {
+        query,
+        source,
+        limit,
+    })? {
+        Reply::Results(results) => Ok(results),
+        reply => Err(Error::new(format!("Unexpected reply: {:?}", reply))),
+    }
+}
+
+pub fn handle_ipc_command(command: IpcCommands) -> Result<()> {
+    match command {
+        IpcCommands::Ping => {
+            let elapsed = ping()?;
+            println!("pong in {:.2}ms", elapsed.as_secs_f64() * 1000.0);
+        }
+        IpcCommands::Search {
+            query,
+            source,
+            json,
+            limit,
+        } => {
+            let results = search(query, source, limit)?;
+            query::print(&results, json, &mut io::stdout().lock())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().mode() & 0o777
+    }
+
+    #[test]
+    fn runtime_dir_is_private() {
+        let base = TempDir::new().unwrap();
+        let uid = unsafe { libc::getuid() };
+
+        let dir = runtime_dir_in(base.path(), uid).unwrap();
+        assert_eq!(dir, base.path().join(format!("frisk-{}", uid)));
+        assert_eq!(mode(&dir), 0o700);
+
+        // Using it again is fine
+        assert_eq!(runtime_dir_in(base.path(), uid).unwrap(), dir);
+    }
+
+    #[test]
+    fn open_runtime_dir_is_tightened() {
+        let base = TempDir::new().unwrap();
+        let uid = unsafe { libc::getuid() };
+        let dir = base.path().join(format!("frisk-{}", uid));
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+
+        runtime_dir_in(base.path(), uid).unwrap();
+        assert_eq!(mode(&dir), 0o700);
+    }
+
+    #[test]
+    fn runtime_dir_of_another_user_is_refused() {
+        let base = TempDir::new().unwrap();
+        let other = unsafe { libc::getuid() } + 1;
+        // Made by us, so not owned by the uid it's named after
+        fs::create_dir(base.path().join(format!("frisk-{}", other))).unwrap();
+
+        let error = runtime_dir_in(base.path(), other).err().unwrap();
+        assert!(error
+            .to_string()
+            .ends_with("isn't a directory owned by you"));
+    }
+
+    #[test]
+    fn runtime_dir_symlink_is_refused() {
+        let base = TempDir::new().unwrap();
+        let uid = unsafe { libc::getuid() };
+        let target = base.path().join("elsewhere");
+        fs::create_dir(&target).unwrap();
+        std::os::unix::fs::symlink(&target, base.path().join(format!("frisk-{}", uid))).unwrap();
+
+        let error = runtime_dir_in(base.path(), uid).err().unwrap();
+        assert!(error
+            .to_string()
+            .ends_with("isn't a directory owned by you"));
+    }
+
+    #[test]
+    fn runtime_dir_file_is_refused() {
+        let base = TempDir::new().unwrap();
+        let uid = unsafe { libc::getuid() };
+        fs::write(base.path().join(format!("frisk-{}", uid)), "").unwrap();
+
+        assert!(runtime_dir_in(base.path(), uid).is_err());
+    }
+
+    #[test]
+    fn runtime_dir_needs_its_base() {
+        let base = TempDir::new().unwrap();
+        let missing = base.path().join("missing");
+
+        let error = runtime_dir_in(&missing, 0).err().unwrap();
+        assert!(error.to_string().starts_with("Failed to create "));
+    }
+}
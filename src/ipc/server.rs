@@ -0,0 +1,397 @@
+use super::{
+    socket_path, IpcMessage, Reply, Request, Response, SearchSource, PROTOCOL_VERSION,
+    REPLY_TIMEOUT, STARTUP_TIMEOUT,
+};
+use crate::core::element::{ElementType, Scope};
+use crate::core::error::{Error, Result};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Set once the picker's main thread is handling requests, with `STARTED`
+/// woken when it is
+static READY: Mutex<bool> = Mutex::new(false);
+static STARTED: Condvar = Condvar::new();
+
+impl SearchSource {
+    /// The element types searched, or None for every type
+    pub fn scope(&self) -> Option<Scope> {
+        match self {
+            SearchSource::All => None,
+            SearchSource::Nixpkgs => Some(Scope::only(ElementType::NixPackage)),
+        }
+    }
+}
+
+/// A message waiting for the picker, answered with `reply`
+pub struct IpcRequest {
+    pub message: IpcMessage,
+    reply_tx: Sender<Reply>,
+}
+
+impl IpcRequest {
+    pub fn reply(self, reply: Reply) {
+        // The connection may have given up waiting
+        self.reply_tx.send(reply).ok();
+    }
+}
+
+/// Remove the socket of a picker that exited without cleaning up. This is only
+/// called while holding the instance lock, so a socket that still accepts
+/// connections belongs to a picker that isn't using the lock.
+pub fn remove_stale_socket(socket_path: &Path) -> Result<()> {
+    match fs::symlink_metadata(socket_path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    if UnixStream::connect(socket_path).is_ok() {
+        return Err(Error::new(format!(
+            "Another picker is listening on {}",
+            socket_path.display()
+        )));
+    }
+
+    crate::log!("Removing stale socket {:?}", socket_path);
+    fs::remove_file(socket_path)?;
+    Ok(())
+}
+
+pub fn start_listener() -> Result<Receiver<IpcRequest>> {
+    let socket_path = socket_path()?;
+
+    remove_stale_socket(&socket_path)?;
+
+    let listener = UnixListener::bind(&socket_path)
+        .map_err(|e| Error::new(format!("Failed to bind IPC socket: {}", e)))?;
+
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        crate::log!("IPC listener started");
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    match peer_uid(&stream) {
+                        Ok(uid) if uid == unsafe { libc::getuid() } => {}
+                        Ok(uid) => {
+                            crate::log!("Rejected IPC connection from uid {}", uid);
+                            continue;
+                        }
+                        Err(e) => {
+                            crate::log!("Rejected IPC connection, no peer credentials: {}", e);
+                            continue;
+                        }
+                    }
+
+                    // A connection waits for the picker's reply, so it mustn't
+                    // hold up the next one
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, tx) {
+                            crate::log!("IPC connection error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    crate::log!("IPC accept error: {}", e);
+                }
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// The user on the other end of a connection
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+/// The user on the other end of a connection
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+fn handle_connection(stream: UnixStream, tx: Sender<IpcRequest>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, reply) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if request.version != PROTOCOL_VERSION => (
+                request.id,
+                Reply::Error(format!(
+                    "Unsupported protocol version {}, expected {}",
+                    request.version, PROTOCOL_VERSION
+                )),
+            ),
+            Ok(request) => {
+                crate::log!("Received IPC message: {:?}", request.message);
+                (request.id, dispatch(request.message, &tx))
+            }
+            Err(e) => {
+                crate::log!("Failed to parse IPC message: {}", e);
+                (
+                    request_id(&line),
+                    Reply::Error(format!("Invalid request: {}", e)),
+                )
+            }
+        };
+
+        let response = Response {
+            version: PROTOCOL_VERSION,
+            id,
+            reply,
+        };
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Start answering requests, including those queued while the picker loaded
+pub fn set_ready() {
+    *READY.lock().unwrap() = true;
+    STARTED.notify_all();
+}
+
+/// Hand a message to the picker and wait for its reply
+fn dispatch(message: IpcMessage, tx: &Sender<IpcRequest>) -> Reply {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    if tx.send(IpcRequest { message, reply_tx }).is_err() {
+        return Reply::Error("The picker has stopped".into());
+    }
+
+    // A picker that is still loading isn't hung, so only time the reply once
+    // it has started
+    let (ready, _) = STARTED
+        .wait_timeout_while(READY.lock().unwrap(), STARTUP_TIMEOUT, |ready| !*ready)
+        .unwrap();
+    if !*ready {
+        return Reply::Error(format!(
+            "The picker is still starting after {}s",
+            STARTUP_TIMEOUT.as_secs()
+        ));
+    }
+    drop(ready);
+    reply_rx
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| Reply::Error("The picker didn't answer".into()))
+}
+
+/// The id of a request that failed to parse, so the reply can still be matched
+fn request_id(line: &str) -> u64 {
+    serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|value| value.get("id")?.as_u64())
+        .unwrap_or(0)
+}
+
+pub fn cleanup() {
+    if let Ok(socket_path) = socket_path() {
+        let _ = fs::remove_file(socket_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::send_message_to_socket;
+    use super::*;
+    use crate::query::QueryResult;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// A listener answering like a picker would: pings with a pong, searches
+    /// with one result and anything else with an error
+    fn serve(dir: &TempDir) -> PathBuf {
+        let path = dir.path().join("frisk.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel::<IpcRequest>();
+        set_ready();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let tx = tx.clone();
+                let stream = stream.unwrap();
+                thread::spawn(move || handle_connection(stream, tx));
+            }
+        });
+        thread::spawn(move || {
+            for request in rx {
+                let reply = match &request.message {
+                    IpcMessage::Ping => Reply::Pong,
+                    IpcMessage::Search { query, .. } => Reply::Results(vec![QueryResult {
+                        name: query.clone(),
+                        value: "/Applications/Safari.app".into(),
+                        element_type: "app".into(),
+                        score: Some(100),
+                    }]),
+                    _ => Reply::Error("Not while testing".into()),
+                };
+                request.reply(reply);
+            }
+        });
+
+        path
+    }
+
+    /// Send a raw request line and read the response to it
+    fn exchange(path: &Path, line: &str) -> Response {
+        let mut stream = UnixStream::connect(path).unwrap();
+        writeln!(stream, "{}", line).unwrap();
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).unwrap();
+        serde_json::from_str(&reply).unwrap()
+    }
+
+    fn error(response: Response) -> String {
+        match response.reply {
+            Reply::Error(e) => e,
+            reply => panic!("Expected an error, got {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn reply_echoes_the_request_id() {
+        let dir = TempDir::new().unwrap();
+        let path = serve(&dir);
+
+        let response = exchange(&path, r#"{"version":1,"id":42,"message":"Ping"}"#);
+        assert_eq!(response.version, PROTOCOL_VERSION);
+        assert_eq!(response.id, 42);
+        assert!(matches!(response.reply, Reply::Pong));
+
+        let reply = send_message_to_socket(&path, &IpcMessage::Ping).unwrap();
+        assert!(matches!(reply, Reply::Pong));
+    }
+
+    #[test]
+    fn other_protocol_versions_are_refused() {
+        let dir = TempDir::new().unwrap();
+        let path = serve(&dir);
+
+        let response = exchange(&path, r#"{"version":99,"id":5,"message":"Ping"}"#);
+        assert_eq!(response.id, 5);
+        assert_eq!(
+            error(response),
+            "Unsupported protocol version 99, expected 1"
+        );
+    }
+
+    #[test]
+    fn unreadable_request_keeps_its_id() {
+        let dir = TempDir::new().unwrap();
+        let path = serve(&dir);
+
+        let response = exchange(&path, r#"{"version":1,"id":7,"message":"Bogus"}"#);
+        assert_eq!(response.id, 7);
+        assert!(error(response).starts_with("Invalid request: "));
+
+        let response = exchange(&path, "not json");
+        assert_eq!(response.id, 0);
+        assert!(error(response).starts_with("Invalid request: "));
+    }
+
+    #[test]
+    fn error_reply_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let path = serve(&dir);
+
+        let message = IpcMessage::Push { items: Vec::new() };
+        let error = send_message_to_socket(&path, &message).err().unwrap();
+        assert_eq!(error.to_string(), "Not while testing");
+    }
+
+    #[test]
+    fn search_returns_the_results() {
+        let dir = TempDir::new().unwrap();
+        let path = serve(&dir);
+
+        let message = IpcMessage::Search {
+            query: "Safari".into(),
+            source: SearchSource::All,
+            limit: Some(5),
+        };
+        let Reply::Results(results) = send_message_to_socket(&path, &message).unwrap() else {
+            panic!("Expected results");
+        };
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "Safari");
+        assert_eq!(results[0].score, Some(100));
+    }
+
+    #[test]
+    fn peer_is_this_user() {
+        let (stream, _peer) = UnixStream::pair().unwrap();
+        assert_eq!(peer_uid(&stream).unwrap(), unsafe { libc::getuid() });
+    }
+
+    fn socket(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("frisk-test-{}-{}.sock", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn stale_socket_is_removed() {
+        let path = socket("stale");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        remove_stale_socket(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn live_socket_is_kept() {
+        let path = socket("live");
+        let _listener = UnixListener::bind(&path).unwrap();
+
+        assert!(remove_stale_socket(&path).is_err());
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_socket_is_fine() {
+        let path = socket("missing");
+        remove_stale_socket(&path).unwrap();
+    }
+}
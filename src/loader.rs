@@ -1,28 +1,597 @@
-use crate::core::element::Element;
-use crate::core::error::Result;
-use bincode::config;
-use std::fs::File;
+use crate::cache::{CacheHeader, CacheLock, CACHE_FORMAT_VERSION, CACHE_MAGIC};
+use crate::core::commands::CommandsConfig;
+use crate::core::element::{Element, ElementList};
+use crate::core::error::{Error, Result};
+use crate::core::history::History;
+use crate::core::source::{Source, SourceFormat, SourceRegistry};
+use bincode::{config, Decode, Encode};
+use std::fs::{self, File};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
-pub fn load_binary_source(name: &str) -> Result<Option<Vec<Element>>> {
-    let cache_dir = crate::cache::cache_dir()?;
-    let path = cache_dir.join(name);
+const LOAD_RETRIES: u32 = 2;
+const LOAD_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+pub fn load_binary_file(path: &Path) -> Result<Vec<Element>> {
+    let (header, elements) = decode_file(path)?;
+    if header.version < CACHE_FORMAT_VERSION {
+        crate::log!(
+            "{:?} uses cache format version {}, rebuild it to upgrade to version {}",
+            path,
+            header.version,
+            CACHE_FORMAT_VERSION
+        );
+    }
+    Ok(elements)
+}
+
+pub fn load_cache<T: Encode + Decode<u32>>(name: &str) -> Result<Option<Vec<T>>> {
+    load_cache_in(&crate::cache::cache_dir()?, name, None)
+}
+
+/// Load a cache whose lock the caller already holds, which migrating it
+/// would otherwise take again and wait on forever
+#[cfg(target_os = "macos")]
+pub fn load_cache_locked<T: Encode + Decode<u32>>(
+    name: &str,
+    lock: &CacheLock,
+) -> Result<Option<Vec<T>>> {
+    load_cache_in(&crate::cache::cache_dir()?, name, Some(lock))
+}
+
+fn load_cache_in<T: Encode + Decode<u32>>(
+    dir: &Path,
+    name: &str,
+    lock: Option<&CacheLock>,
+) -> Result<Option<Vec<T>>> {
+    let path = dir.join(name);
 
     if !path.exists() {
         return Ok(None);
     }
 
-    load_binary_file(&path).map(Some)
+    let (header, items) = match decode_with_retry(&path) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            let backup = crate::cache::backup_path(&path);
+            if !backup.exists() {
+                return Err(e);
+            }
+            crate::log!(
+                "Failed to read {}, falling back to last good copy: {}",
+                name,
+                e
+            );
+            let (_, items) = decode_file(&backup)?;
+            return Ok(Some(items));
+        }
+    };
+
+    // Caches we own are rewritten in the current format once migrated
+    if header.version < CACHE_FORMAT_VERSION {
+        match migrate_cache(dir, name, &path, lock) {
+            Ok(migrated) => return Ok(Some(migrated)),
+            Err(e) => crate::log!("Failed to migrate {}: {}", name, e),
+        }
+    }
+
+    Ok(Some(items))
 }
 
-pub fn load_binary_file(path: &Path) -> Result<Vec<Element>> {
+/// Rewrite a cache in the current format, holding its lock from the read to
+/// the write so an update a daemon makes in between isn't lost. `held` is the
+/// lock if the caller already has it.
+fn migrate_cache<T: Encode + Decode<u32>>(
+    dir: &Path,
+    name: &str,
+    path: &Path,
+    held: Option<&CacheLock>,
+) -> Result<Vec<T>> {
+    let _lock = match held {
+        Some(_) => None,
+        None => Some(crate::cache::lock_cache_in(dir, name)?),
+    };
+
+    // Read it again, as it may have changed before we got the lock
+    let (header, items) = decode_file(path)?;
+    if header.version < CACHE_FORMAT_VERSION {
+        crate::log!(
+            "Migrating {} from cache format version {} to {}",
+            name,
+            header.version,
+            CACHE_FORMAT_VERSION
+        );
+        crate::cache::save_cache_in(dir, name, &items)?;
+    }
+
+    Ok(items)
+}
+
+// A writer predating atomic saves may still be mid-write, so give it a moment.
+// Only a file that couldn't be read or was cut short might read differently.
+fn decode_with_retry<T: Decode<u32>>(path: &Path) -> Result<(CacheHeader, Vec<T>)> {
+    let mut attempt = 0;
+    loop {
+        match read_cache_file(path) {
+            Ok(decoded) => return Ok(decoded),
+            Err(ReadError::Incomplete(e)) if attempt < LOAD_RETRIES => {
+                crate::log!("Retrying {}: {}", path.display(), e);
+                attempt += 1;
+                thread::sleep(LOAD_RETRY_DELAY);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Why a cache file couldn't be read
+enum ReadError {
+    /// The file couldn't be read or ended early, as one still being written can
+    Incomplete(Error),
+    /// The file is from a newer frisk or isn't a cache at all
+    Invalid(Error),
+}
+
+impl From<ReadError> for Error {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Incomplete(e) | ReadError::Invalid(e) => e,
+        }
+    }
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(err: std::io::Error) -> Self {
+        ReadError::Incomplete(err.into())
+    }
+}
+
+impl From<bincode::error::DecodeError> for ReadError {
+    fn from(err: bincode::error::DecodeError) -> Self {
+        match err {
+            bincode::error::DecodeError::UnexpectedEnd { .. } => ReadError::Incomplete(err.into()),
+            _ => ReadError::Invalid(err.into()),
+        }
+    }
+}
+
+fn decode_file<T: Decode<u32>>(path: &Path) -> Result<(CacheHeader, Vec<T>)> {
+    Ok(read_cache_file(path)?)
+}
+
+/// Items are decoded with the file's format version as context, so types that
+/// changed shape can still read older files
+fn read_cache_file<T: Decode<u32>>(
+    path: &Path,
+) -> std::result::Result<(CacheHeader, Vec<T>), ReadError> {
     let mut file = File::open(path)?;
     let mut bytes = Vec::new();
     file.read_to_end(&mut bytes)?;
 
     let config = config::standard();
-    let (elements, _): (Vec<Element>, usize) = bincode::decode_from_slice(&bytes, config)?;
+
+    if !bytes.starts_with(&CACHE_MAGIC) {
+        // Version 0: a bare Vec<T> without a header
+        let (items, _): (Vec<T>, usize) =
+            bincode::decode_from_slice_with_context(&bytes, config, 0)?;
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            version: 0,
+            source: source_name(path),
+            generated_at: 0,
+            count: items.len() as u64,
+        };
+        return Ok((header, items));
+    }
+
+    let ((_, version), _): (([u8; 4], u32), usize) = bincode::decode_from_slice(&bytes, config)?;
+    if version > CACHE_FORMAT_VERSION {
+        return Err(ReadError::Invalid(Error::new(format!(
+            "{} uses cache format version {}, but this frisk only understands up to version {}. \
+             Upgrade frisk or regenerate the file.",
+            path.display(),
+            version,
+            CACHE_FORMAT_VERSION
+        ))));
+    }
+
+    let (header, read): (CacheHeader, usize) = bincode::decode_from_slice(&bytes, config)?;
+    let (items, _): (Vec<T>, usize) =
+        bincode::decode_from_slice_with_context(&bytes[read..], config, version)?;
+
+    if items.len() as u64 != header.count {
+        return Err(ReadError::Invalid(Error::new(format!(
+            "{} is corrupt: header lists {} items but {} were decoded",
+            path.display(),
+            header.count,
+            items.len()
+        ))));
+    }
+
+    crate::log!(
+        "Read {} {} items from {} (format version {}, generated at {})",
+        header.count,
+        header.source,
+        path.display(),
+        header.version,
+        header.generated_at
+    );
+
+    Ok((header, items))
+}
+
+fn source_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn load_elements(
+    registry: &SourceRegistry,
+    names: &[String],
+    paths: &[PathBuf],
+) -> Result<ElementList> {
+    let mut elements = ElementList::new();
+    elements.set_history(History::load());
+    elements.set_type_ranking(registry.type_ranking().to_vec());
+    elements.loaded_sources = names.to_vec();
+    elements.loaded_paths = paths.to_vec();
+
+    for source in registry.select(names, paths.is_empty()) {
+        let loaded = crate::cache::cache_dir()
+            .and_then(|cache_dir| load_source(source, &cache_dir, &mut elements));
+        match loaded {
+            Ok(Some(count)) => {
+                crate::log!("Loaded {} items from source {}", count, source.name);
+            }
+            Ok(None) => {
+                let file = source
+                    .path
+                    .clone()
+                    .unwrap_or_else(|| format!("{}.bin", source.name));
+                eprintln!("Warning: {} specified but {} not found", source.name, file);
+                if let Some(hint) = &source.hint {
+                    eprintln!("Run: {}", hint);
+                }
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to load source {}: {}", source.name, e);
+            }
+        }
+    }
+
+    for source_path in paths {
+        match load_binary_file(source_path) {
+            Ok(items) => {
+                let count = items.len();
+                for item in items {
+                    elements.add(item);
+                }
+                crate::log!("Loaded {} items from {:?}", count, source_path);
+            }
+            Err(e) => {
+                crate::log!("Failed to load source {:?}: {}", source_path, e);
+            }
+        }
+    }
 
     Ok(elements)
 }
+
+/// Add a source's elements, returning how many there were or None if its file
+/// doesn't exist. Relative paths are in `cache_dir` whatever the format.
+fn load_source(
+    source: &Source,
+    cache_dir: &Path,
+    elements: &mut ElementList,
+) -> Result<Option<usize>> {
+    let path = match source.path.as_deref().map(expand_home) {
+        // Bincode paths stay relative so they're read as caches below
+        Some(path) if path.is_relative() && source.format != SourceFormat::Bincode => {
+            Some(cache_dir.join(path))
+        }
+        path => path,
+    };
+
+    let items = match (source.format, path) {
+        (SourceFormat::Bincode, None) => {
+            load_cache_in(cache_dir, &format!("{}.bin", source.name), None)
+        }
+        // Read as a cache, so it's migrated and falls back to its backup
+        (SourceFormat::Bincode, Some(path)) if path.is_relative() => {
+            load_cache_in(cache_dir, &path.to_string_lossy(), None)
+        }
+        (SourceFormat::Commands, None) => {
+            let commands = CommandsConfig::load()?;
+            elements.add_commands(&commands, source.ranking);
+            return Ok(Some(commands.command.len()));
+        }
+        (SourceFormat::Lines, None) => Err(Error::new(format!(
+            "Source {} uses the lines format but has no path",
+            source.name
+        ))),
+        (_, Some(path)) if !path.exists() => Ok(None),
+        (SourceFormat::Bincode, Some(path)) => load_binary_file(&path).map(Some),
+        (SourceFormat::Commands, Some(path)) => {
+            let commands = CommandsConfig::load_from(&path)?;
+            elements.add_commands(&commands, source.ranking);
+            return Ok(Some(commands.command.len()));
+        }
+        (SourceFormat::Lines, Some(path)) => {
+            let content = fs::read_to_string(&path)?;
+            Ok(Some(
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| Element::new_text(line.to_string()))
+                    .collect(),
+            ))
+        }
+    }?;
+
+    let count = items.as_ref().map(Vec::len);
+    if let Some(items) = items {
+        elements.add_source(items, source.ranking);
+    }
+    Ok(count)
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{lock_cache_in, save_cache_in};
+    use crate::core::element::ElementType;
+    use tempfile::TempDir;
+
+    fn elements() -> Vec<Element> {
+        let mut confirmed = Element::new_system_command("Sleep".into(), "pmset sleepnow".into());
+        confirmed.confirm = true;
+        vec![
+            Element::new("Safari".into(), "/Applications/Safari.app".into()),
+            confirmed,
+        ]
+    }
+
+    /// Elements as versions 0 and 1 wrote them, before `confirm` existed
+    fn old_elements() -> Vec<(Box<str>, Box<str>, ElementType)> {
+        elements()
+            .into_iter()
+            .map(|e| (e.name, e.value, e.element_type))
+            .collect()
+    }
+
+    fn encode_file<T: Encode>(
+        dir: &TempDir,
+        name: &str,
+        header: Option<CacheHeader>,
+        items: &T,
+    ) -> PathBuf {
+        let config = config::standard();
+        let mut bytes = Vec::new();
+        if let Some(header) = header {
+            bytes = bincode::encode_to_vec(header, config).unwrap();
+        }
+        bytes.extend(bincode::encode_to_vec(items, config).unwrap());
+
+        let path = dir.path().join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn header(version: u32, count: u64) -> CacheHeader {
+        CacheHeader {
+            version,
+            count,
+            ..CacheHeader::new("test", 0)
+        }
+    }
+
+    fn names(elements: &[Element]) -> Vec<&str> {
+        elements.iter().map(|e| e.name.as_ref()).collect()
+    }
+
+    #[test]
+    fn header_round_trips() {
+        let dir = TempDir::new().unwrap();
+        save_cache_in(dir.path(), "round-trip.bin", &elements()).unwrap();
+        let (header, items): (_, Vec<Element>) =
+            decode_file(&dir.path().join("round-trip.bin")).unwrap();
+
+        assert_eq!(header.magic, CACHE_MAGIC);
+        assert_eq!(header.version, CACHE_FORMAT_VERSION);
+        assert_eq!(header.source, "round-trip");
+        assert_eq!(header.count, 2);
+        assert_eq!(names(&items), ["Safari", "Sleep"]);
+        assert!(items[1].confirm);
+    }
+
+    #[test]
+    fn confirm_survives_commands_and_the_cache() {
+        let dir = TempDir::new().unwrap();
+        let commands: CommandsConfig = toml::from_str(
+            r#"
+            [[command]]
+            name = "Shut Down"
+            action = "shutdown -h now"
+            confirm = true
+
+            [[command]]
+            name = "Lock"
+            action = "pmset displaysleepnow"
+            "#,
+        )
+        .unwrap();
+        save_cache_in(dir.path(), "confirm.bin", &commands.to_elements()).unwrap();
+
+        let items = load_cache_in::<Element>(dir.path(), "confirm.bin", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&items), ["Shut Down", "Lock"]);
+        assert!(items[0].confirm);
+        assert!(!items[1].confirm);
+    }
+
+    #[test]
+    fn headerless_cache_is_upgraded() {
+        let dir = TempDir::new().unwrap();
+        let path = encode_file(&dir, "v0.bin", None, &old_elements());
+
+        let items = load_cache_in::<Element>(dir.path(), "v0.bin", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&items), ["Safari", "Sleep"]);
+        assert!(!items[1].confirm);
+
+        let (header, _): (_, Vec<Element>) = decode_file(&path).unwrap();
+        assert_eq!(header.version, CACHE_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn version_1_elements_decode_without_confirm() {
+        let dir = TempDir::new().unwrap();
+        let path = encode_file(&dir, "v1.bin", Some(header(1, 2)), &old_elements());
+
+        let items = load_binary_file(&path).unwrap();
+        assert_eq!(names(&items), ["Safari", "Sleep"]);
+        assert_eq!(items[1].element_type, ElementType::SystemCommand);
+        assert!(!items[1].confirm);
+    }
+
+    #[test]
+    fn migrating_while_holding_the_lock_does_not_deadlock() {
+        let dir = TempDir::new().unwrap();
+        let path = encode_file(&dir, "v1-locked.bin", Some(header(1, 2)), &old_elements());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cache = dir.path().to_path_buf();
+        std::thread::spawn(move || {
+            let lock = lock_cache_in(&cache, "v1-locked.bin").unwrap();
+            let items: Option<Vec<Element>> =
+                load_cache_in(&cache, "v1-locked.bin", Some(&lock)).unwrap();
+            tx.send(items.unwrap().len()).unwrap();
+        });
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(2));
+        let (header, _): (_, Vec<Element>) = decode_file(&path).unwrap();
+        assert_eq!(header.version, CACHE_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn future_version_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let version = CACHE_FORMAT_VERSION + 1;
+        let path = encode_file(&dir, "future.bin", Some(header(version, 2)), &elements());
+
+        let error = load_binary_file(&path).err().unwrap().to_string();
+        assert!(
+            error.contains(&format!("cache format version {version}")),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn future_version_is_not_retried() {
+        let dir = TempDir::new().unwrap();
+        let version = CACHE_FORMAT_VERSION + 1;
+        let path = encode_file(
+            &dir,
+            "future-retry.bin",
+            Some(header(version, 2)),
+            &elements(),
+        );
+
+        let start = std::time::Instant::now();
+        assert!(decode_with_retry::<Element>(&path).is_err());
+        assert!(start.elapsed() < LOAD_RETRY_DELAY);
+    }
+
+    #[test]
+    fn count_mismatch_is_corrupt() {
+        let dir = TempDir::new().unwrap();
+        let path = encode_file(
+            &dir,
+            "short.bin",
+            Some(header(CACHE_FORMAT_VERSION, 3)),
+            &elements(),
+        );
+
+        let error = load_binary_file(&path).err().unwrap().to_string();
+        assert!(
+            error.contains("header lists 3 items but 2 were decoded"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn unreadable_cache_falls_back_to_backup() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("broken.bin"), b"FRSK garbage").unwrap();
+        save_cache_in(dir.path(), "broken.bin.bak", &elements()).unwrap();
+
+        let items = load_cache_in::<Element>(dir.path(), "broken.bin", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&items), ["Safari", "Sleep"]);
+    }
+
+    fn source(name: &str, path: &str, format: SourceFormat) -> Source {
+        Source {
+            name: name.into(),
+            path: Some(path.into()),
+            format,
+            enabled: false,
+            priority: 0,
+            hint: None,
+            ranking: Default::default(),
+        }
+    }
+
+    #[test]
+    fn relative_paths_are_in_the_cache_dir_for_every_format() {
+        let dir = TempDir::new().unwrap();
+        save_cache_in(dir.path(), "custom.bin", &elements()).unwrap();
+        fs::write(dir.path().join("bookmarks.txt"), "docs\n\nrepo\n").unwrap();
+        fs::write(
+            dir.path().join("extra.toml"),
+            "[[command]]\nname = \"Lock\"\naction = \"pmset displaysleepnow\"\n",
+        )
+        .unwrap();
+
+        let mut elements = ElementList::new();
+        for (source, count) in [
+            (source("custom", "custom.bin", SourceFormat::Bincode), 2),
+            (source("bookmarks", "bookmarks.txt", SourceFormat::Lines), 2),
+            (source("extra", "extra.toml", SourceFormat::Commands), 1),
+        ] {
+            let loaded = load_source(&source, dir.path(), &mut elements).unwrap();
+            assert_eq!(loaded, Some(count), "{}", source.name);
+        }
+        assert_eq!(
+            names(&elements.inner),
+            ["Safari", "Sleep", "docs", "repo", "Lock"]
+        );
+    }
+
+    #[test]
+    fn missing_source_file_is_none() {
+        let dir = TempDir::new().unwrap();
+        let mut elements = ElementList::new();
+
+        for format in [SourceFormat::Bincode, SourceFormat::Lines] {
+            let source = source("missing", "missing.txt", format);
+            assert_eq!(
+                load_source(&source, dir.path(), &mut elements).unwrap(),
+                None
+            );
+        }
+    }
+}
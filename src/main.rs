@@ -1,11 +1,20 @@
 mod cache;
 mod cli;
+mod config_command;
 mod core;
+mod dmenu;
+#[cfg(target_os = "macos")]
 mod gui;
+// Only built for its tests off macOS
+#[cfg(any(target_os = "macos", test))]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
 mod instance;
 mod ipc;
 mod loader;
+#[cfg(target_os = "macos")]
 mod picker;
+mod push;
+mod query;
 mod services;
 
 #[macro_use]
@@ -23,21 +32,46 @@ fn main() -> Result<()> {
             services::handle_service_command(command)?;
             Ok(())
         }
+        Some(Commands::Query {
+            query,
+            sources,
+            json,
+            limit,
+        }) => query::run(&query, &sources, json, limit, cli.config),
+        Some(Commands::Config { command }) => {
+            config_command::handle_config_command(command, cli.config)
+        }
+        Some(Commands::Push {
+            name,
+            value,
+            element_type,
+            action,
+            ..
+        }) => push::run(name, value, element_type, action),
+        Some(Commands::Ipc { command }) => ipc::handle_ipc_command(command),
         Some(Commands::Daemon { command }) => {
             use cli::DaemonCommands;
             match command {
                 DaemonCommands::Apps => services::apps::run(),
                 DaemonCommands::Homebrew => services::homebrew::run(),
+                #[cfg(target_os = "macos")]
                 DaemonCommands::Clipboard => services::clipboard::run(),
+                #[cfg(not(target_os = "macos"))]
+                DaemonCommands::Clipboard => Err(core::error::Error::new(
+                    "The clipboard daemon only runs on macOS",
+                )),
                 DaemonCommands::Nixpkgs => services::nixpkgs::run(),
                 DaemonCommands::Dictionary => services::dictionary::run(),
             }
         }
+        None if cli.dmenu => dmenu::run(cli),
+        #[cfg(target_os = "macos")]
         None => {
             let result = gui::run(cli);
-            instance::cleanup_lock_file();
-            ipc::cleanup();
+            instance::cleanup();
             result
         }
+        #[cfg(not(target_os = "macos"))]
+        None => Err(core::error::Error::new("The picker only runs on macOS")),
     }
 }
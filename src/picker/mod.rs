@@ -6,21 +6,24 @@ mod window;
 use crate::core::config::Config;
 use crate::core::element::ElementList;
 use crate::core::error::{Error, Result};
-use crate::ipc::IpcMessage;
+use crate::ipc::server::IpcRequest;
 use objc2::MainThreadMarker;
 use objc2_app_kit::{NSAccessibility, NSApplication, NSApplicationActivationPolicy};
 use std::sync::mpsc::Receiver;
 use window::create_window;
 
+pub use state::PickerMode;
+
 pub fn run(
     config: Config,
     elements: ElementList,
-    ipc_rx: Option<Receiver<IpcMessage>>,
+    ipc_rx: Option<Receiver<IpcRequest>>,
+    mode: PickerMode,
 ) -> Result<()> {
     let mtm =
         MainThreadMarker::new().ok_or_else(|| Error::new("Must be called from main thread"))?;
 
-    let _window = match create_window(mtm, config, elements, ipc_rx) {
+    let _window = match create_window(mtm, config, elements, ipc_rx, mode) {
         Ok(window) => window,
         Err(error) => panic!("Error creating the window: {error:?}"),
     };
@@ -30,6 +33,7 @@ pub fn run(
     app.activate();
     app.setAccessibilityFrontmost(true);
 
+    crate::ipc::server::set_ready();
     app.run();
     Ok(())
 }
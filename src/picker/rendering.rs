@@ -1,3 +1,4 @@
+use crate::core::element::highlight_ranges;
 use objc2::msg_send;
 use objc2::rc::Retained;
 use objc2::runtime::AnyObject;
@@ -5,6 +6,18 @@ use objc2_app_kit::{NSBezierPath, NSColor, NSFont};
 use objc2_foundation::{NSPoint, NSRange, NSRect, NSSize, NSString};
 
 pub fn draw_text(text: &str, x: f64, y: f64, color: &Retained<NSColor>, font: &Retained<NSFont>) {
+    draw_highlighted_text(text, x, y, color, font, &[], color);
+}
+
+pub fn draw_highlighted_text(
+    text: &str,
+    x: f64,
+    y: f64,
+    color: &Retained<NSColor>,
+    font: &Retained<NSFont>,
+    positions: &[u32],
+    highlight_color: &Retained<NSColor>,
+) {
     let ns_text = NSString::from_str(text);
     let char_count = ns_text.length();
     let full_range = NSRange::new(0, char_count);
@@ -15,11 +28,6 @@ pub fn draw_text(text: &str, x: f64, y: f64, color: &Retained<NSColor>, font: &R
             static NSFontAttributeName: &'static AnyObject;
         }
 
-        let opaque_color: Retained<NSColor> = msg_send![
-            color,
-            colorWithAlphaComponent: 1.0
-        ];
-
         let attr_string: Retained<AnyObject> = msg_send![
             msg_send![objc2::class!(NSMutableAttributedString), alloc],
             initWithString: &*ns_text
@@ -35,10 +43,22 @@ pub fn draw_text(text: &str, x: f64, y: f64, color: &Retained<NSColor>, font: &R
         let () = msg_send![
             &*attr_string,
             addAttribute: NSForegroundColorAttributeName,
-            value: &*opaque_color,
+            value: &**color,
             range: full_range
         ];
 
+        if !positions.is_empty() {
+            for range in highlight_ranges(text, positions) {
+                let range = NSRange::new(range.start, range.len());
+                let () = msg_send![
+                    &*attr_string,
+                    addAttribute: NSForegroundColorAttributeName,
+                    value: &**highlight_color,
+                    range: range
+                ];
+            }
+        }
+
         let point = NSPoint::new(x, y);
         let () = msg_send![&*attr_string, drawAtPoint: point];
     }
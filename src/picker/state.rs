@@ -1,16 +1,65 @@
+use crate::cli::{split_words, Cli};
 use crate::core::calculator::Calculator;
-use crate::core::config::Config;
-use crate::core::element::{Element, ElementList, ElementType};
+use crate::core::commands::CommandsConfig;
+use crate::core::config::{self, Config};
+use crate::core::element::{Element, ElementList, ElementType, Scope};
 use crate::core::error::{Error, Result};
+use crate::core::exec::{self, Background};
+use crate::core::history::History;
+use crate::core::placeholder;
+use crate::core::script::{self, ScriptItem};
+use crate::core::watch::FileWatcher;
+use clap::Parser;
 use objc2::MainThreadMarker;
 use objc2_app_kit::{NSApplication, NSPasteboard, NSPasteboardTypeString};
 use objc2_foundation::NSString;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::process::Command;
 
+/// A script filter or `wait` command running off the main thread, finished
+/// by `poll_job`
+struct Job {
+    process: Background,
+    kind: JobKind,
+    /// Shown at the bottom of the window until it finishes
+    status: String,
+}
+
+enum JobKind {
+    /// Its output replaces the elements, and the element is recorded as
+    /// launched if it succeeds
+    ScriptFilter(Element),
+    /// The picker closes and the element is recorded as launched if it succeeds
+    Command(Element),
+}
+
+/// What the elements were built from, so a config reload rebuilds the same list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Listing {
+    /// Sources, loaded again from the names and files they came from
+    Sources,
+    /// A script filter's output, kept rather than running the script again
+    ScriptFilter,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickerMode {
+    /// Run the selected element's type-specific action
+    Launcher,
+    /// Print the selected element's value to stdout, like dmenu
+    Dmenu,
+}
+
 pub struct AppState {
     pub config: Config,
     pub elements: ElementList,
     pub filtered_indices: Vec<usize>,
+    /// Highlighted characters by row of `filtered_indices`, worked out when
+    /// the row is first drawn
+    pub match_positions: HashMap<usize, Vec<u32>>,
     pub selected_index: usize,
     pub scroll_offset: usize,
     pub query: String,
@@ -18,10 +67,25 @@ pub struct AppState {
     pub should_exit: bool,
     pub dynamic_max_results: usize,
     pub menubar_height: f64,
+    window_height: f64,
     calculator: Option<Calculator>,
-    pub calculator_result: Option<Element>,
+    /// Calculator answer or quicklink shown above the search results
+    pub top_result: Option<Element>,
     pub prompt_query_cache: String,
     pub cursor_text_cache: String,
+    pub mode: PickerMode,
+    pub scope: Option<Scope>,
+    scope_prefix_len: usize,
+    /// A command waiting for its `{input}` argument to be typed
+    pending_command: Option<usize>,
+    /// An element asking for confirmation before it runs
+    pub confirming: Option<usize>,
+    /// Why the last action failed, shown until the query changes
+    pub status: Option<String>,
+    /// Reloads config.toml and commands.toml when they change
+    watcher: Option<FileWatcher>,
+    job: Option<Job>,
+    listing: Listing,
 }
 
 impl AppState {
@@ -30,14 +94,16 @@ impl AppState {
         elements: ElementList,
         window_height: f64,
         menubar_height: f64,
+        mode: PickerMode,
     ) -> Self {
-        let font_size = config.font_size as f64;
-        let max_results = Self::calculate_max_results(window_height, font_size, menubar_height);
+        let max_results =
+            Self::calculate_max_results(window_height, config.font_size, menubar_height);
 
         let mut state = Self {
             config,
             elements,
             filtered_indices: Vec::new(),
+            match_positions: HashMap::new(),
             selected_index: 0,
             scroll_offset: 0,
             query: String::with_capacity(32),
@@ -45,11 +111,24 @@ impl AppState {
             should_exit: false,
             dynamic_max_results: max_results,
             menubar_height,
+            window_height,
             calculator: None,
-            calculator_result: None,
+            top_result: None,
             prompt_query_cache: String::with_capacity(64),
             cursor_text_cache: String::with_capacity(64),
+            mode,
+            scope: None,
+            scope_prefix_len: 0,
+            pending_command: None,
+            confirming: None,
+            status: None,
+            watcher: None,
+            job: None,
+            listing: Listing::Sources,
         };
+        if mode == PickerMode::Launcher {
+            state.watch_config();
+        }
         state.update_search();
         state
     }
@@ -72,43 +151,168 @@ impl AppState {
     }
 
     pub fn update_search(&mut self) {
-        // Normal fuzzy search with calculator
-        let indices = self.elements.search(&self.query);
-
-        if self.calculator.is_none() && !self.query.is_empty() {
-            self.calculator = Calculator::new().ok();
+        self.confirming = None;
+        self.status = self.job.as_ref().map(|job| job.status.clone());
+
+        if let Some(idx) = self.pending_command {
+            // The query is the pending command's argument
+            self.scope = None;
+            self.scope_prefix_len = 0;
+            self.filtered_indices = vec![idx];
+            self.match_positions = HashMap::from([(0, Vec::new())]);
+            self.top_result = None;
+            self.selected_index = 0;
+            self.scroll_offset = 0;
+            return;
         }
 
-        self.calculator_result = None;
-        if let Some(calc) = &mut self.calculator {
-            if !self.query.is_empty() {
-                if let Some(result) = calc.evaluate(&self.query) {
-                    self.calculator_result = Some(Element {
-                        name: result.clone().into_boxed_str(),
-                        value: result.into_boxed_str(),
-                        element_type: crate::core::element::ElementType::CalculatorResult,
-                    });
+        self.update_scope();
+        let query = &self.query[self.scope_prefix_len..];
+        let calculator_only = self.scope == Some(Scope::only(ElementType::CalculatorResult));
+
+        // Normal fuzzy search with calculator
+        let matches = if calculator_only {
+            Vec::new()
+        } else {
+            self.elements.search_in(query, self.scope.as_ref())
+        };
+        let mut indices: Vec<usize> = matches.into_iter().map(|m| m.index).collect();
+        let mut commands_typed = 0;
+
+        // Commands named at the start of the query take the rest as their argument
+        let command_scope = match &self.scope {
+            Some(scope) => scope.contains(&ElementType::SystemCommand),
+            None => true,
+        };
+        if query.contains(' ') && command_scope {
+            for (idx, element) in self.elements.inner.iter().enumerate().rev() {
+                if matches!(
+                    element.element_type,
+                    ElementType::SystemCommand | ElementType::ScriptFilter
+                ) && placeholder::is_parameterized(&element.value)
+                    && placeholder::typed_argument(&element.name, query).is_some()
+                {
+                    if let Some(existing) = indices.iter().position(|&i| i == idx) {
+                        indices.remove(existing);
+                    }
+                    indices.insert(0, idx);
+                    commands_typed += 1;
                 }
             }
         }
 
+        self.top_result = None;
+        // dmenu prints what was picked from stdin, so nothing goes above it
+        if self.mode == PickerMode::Launcher {
+            self.update_top_result(calculator_only);
+        }
+
+        // Commands whose argument is being typed are highlighted in full
+        self.match_positions = indices[..commands_typed]
+            .iter()
+            .enumerate()
+            .map(|(row, &idx)| {
+                let len = self.elements.inner[idx].name.chars().count() as u32;
+                (row, (0..len).collect())
+            })
+            .collect();
         self.filtered_indices = indices;
         self.selected_index = 0;
         self.scroll_offset = 0;
     }
 
+    /// The quicklink for the query, or else the calculator's answer to it
+    fn update_top_result(&mut self, calculator_only: bool) {
+        let query = &self.query[self.scope_prefix_len..];
+        if self.calculator.is_none() && !query.is_empty() {
+            self.calculator = Calculator::new().ok();
+        }
+
+        if self.scope.is_none() {
+            self.top_result = self
+                .config
+                .quicklinks
+                .iter()
+                .find_map(|quicklink| quicklink.resolve(query));
+        }
+        if self.top_result.is_none() && (self.scope.is_none() || calculator_only) {
+            if let Some(calc) = &mut self.calculator {
+                if !query.is_empty() {
+                    if let Some(result) = calc.evaluate(query) {
+                        self.top_result = Some(Element {
+                            name: result.clone().into_boxed_str(),
+                            value: result.into_boxed_str(),
+                            element_type: crate::core::element::ElementType::CalculatorResult,
+                            confirm: false,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_scope(&mut self) {
+        // Items from stdin are only ever matched as text
+        if self.mode == PickerMode::Dmenu {
+            self.scope = None;
+            self.scope_prefix_len = 0;
+            return;
+        }
+
+        let scope = config::find_prefix(&self.config.prefixes, &self.query, &self.elements);
+
+        (self.scope, self.scope_prefix_len) = match scope {
+            Some((prefix, scope)) => (Some(scope.clone()), prefix.len()),
+            None => (None, 0),
+        };
+    }
+
     pub fn update_string_caches(&mut self) {
+        // The typed prefix is replaced by a label for the active scope
+        let pending = self
+            .pending_command
+            .and_then(|idx| self.elements.inner.get(idx));
+        let scope_label = match (pending, &self.scope) {
+            (Some(command), _) => format!("[{}] ", command.name),
+            (None, Some(scope)) => format!("[{}] ", scope.label()),
+            (None, None) => String::new(),
+        };
+        let visible_start = self.scope_prefix_len;
+
         self.prompt_query_cache.clear();
         self.prompt_query_cache.push_str(&self.config.prompt);
-        self.prompt_query_cache.push_str(&self.query);
+        self.prompt_query_cache.push_str(&scope_label);
+        self.prompt_query_cache
+            .push_str(&self.query[visible_start..]);
 
         self.cursor_text_cache.clear();
         self.cursor_text_cache.push_str(&self.config.prompt);
-        self.cursor_text_cache
-            .push_str(&self.query[..self.cursor_position]);
+        self.cursor_text_cache.push_str(&scope_label);
+        if self.cursor_position > visible_start {
+            self.cursor_text_cache
+                .push_str(&self.query[visible_start..self.cursor_position]);
+        }
+    }
+
+    /// Work out the highlighted characters of the rows on screen
+    pub fn update_match_positions(&mut self) {
+        let query = &self.query[self.scope_prefix_len..];
+        let top_rows = self.top_result.is_some() as usize;
+        let start = self.scroll_offset.saturating_sub(top_rows);
+        let end = (self.scroll_offset + self.dynamic_max_results)
+            .saturating_sub(top_rows)
+            .min(self.filtered_indices.len());
+
+        for row in start..end {
+            let idx = self.filtered_indices[row];
+            self.match_positions
+                .entry(row)
+                .or_insert_with(|| self.elements.match_positions(query, idx));
+        }
     }
 
     pub fn nav_up(&mut self) {
+        self.confirming = None;
         if self.selected_index > 0 {
             self.selected_index -= 1;
             if self.selected_index < self.scroll_offset {
@@ -118,8 +322,8 @@ impl AppState {
     }
 
     pub fn nav_down(&mut self) {
-        let total_results =
-            (self.calculator_result.is_some() as usize) + self.filtered_indices.len();
+        self.confirming = None;
+        let total_results = (self.top_result.is_some() as usize) + self.filtered_indices.len();
         if self.selected_index < total_results.saturating_sub(1) {
             self.selected_index += 1;
             let visible_end = self.scroll_offset + self.dynamic_max_results;
@@ -129,13 +333,71 @@ impl AppState {
         }
     }
 
+    pub fn selected_element(&self) -> Option<&Element> {
+        if self.selected_index == 0 && self.top_result.is_some() {
+            return self.top_result.as_ref();
+        }
+
+        let app_idx = if self.top_result.is_some() {
+            self.selected_index - 1
+        } else {
+            self.selected_index
+        };
+
+        self.filtered_indices
+            .get(app_idx)
+            .and_then(|&idx| self.elements.inner.get(idx))
+    }
+
+    fn print_selected(&mut self) -> Result<()> {
+        // Like dmenu, fall back to the typed text when nothing matches
+        let output = match self.selected_element() {
+            Some(element) => element.value.to_string(),
+            None => self.query.clone(),
+        };
+        self.print(&output)
+    }
+
+    fn print(&mut self, output: &str) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", output)?;
+        stdout.flush()?;
+
+        self.should_exit = true;
+        Ok(())
+    }
+
+    /// Print the typed query rather than the selection in dmenu mode. The
+    /// launcher has nothing to do with bare text, so it runs the selection.
+    pub fn execute_query(&mut self) -> Result<()> {
+        if self.mode != PickerMode::Dmenu {
+            return self.execute_selected();
+        }
+        let query = self.query[self.scope_prefix_len..].to_string();
+        self.print(&query)
+    }
+
     pub fn execute_selected(&mut self) -> Result<()> {
-        if self.selected_index == 0 && self.calculator_result.is_some() {
-            if let Some(calc_result) = &self.calculator_result {
-                crate::log!("Copying calculator result: {}", calc_result.value);
+        if self.mode == PickerMode::Dmenu {
+            return self.print_selected();
+        }
+
+        if self.selected_index == 0 && self.top_result.is_some() {
+            if let Some(result) = &self.top_result {
+                if result.element_type == ElementType::Quicklink {
+                    crate::log!("Opening quicklink: {}", result.value);
+                    Command::new("open")
+                        .arg(result.value.as_ref())
+                        .spawn()
+                        .map_err(|e| Error::new(format!("Failed to open URL: {}", e)))?;
+                    self.should_exit = true;
+                    return Ok(());
+                }
+
+                crate::log!("Copying calculator result: {}", result.value);
                 let pasteboard = NSPasteboard::generalPasteboard();
                 pasteboard.clearContents();
-                let ns_string = NSString::from_str(&calc_result.value);
+                let ns_string = NSString::from_str(&result.value);
                 if unsafe { pasteboard.setString_forType(&ns_string, NSPasteboardTypeString) } {
                     self.should_exit = true;
                 } else {
@@ -143,14 +405,24 @@ impl AppState {
                 }
             }
         } else {
-            let app_idx = if self.calculator_result.is_some() {
+            let app_idx = if self.top_result.is_some() {
                 self.selected_index - 1
             } else {
                 self.selected_index
             };
 
             if let Some(&idx) = self.filtered_indices.get(app_idx) {
-                if let Some(element) = self.elements.inner.get(idx) {
+                if let Some(element) = self.elements.inner.get(idx).cloned() {
+                    // Input for a pending command is typed after confirming
+                    if element.confirm
+                        && self.confirming != Some(idx)
+                        && self.pending_command != Some(idx)
+                    {
+                        self.confirming = Some(idx);
+                        return Ok(());
+                    }
+                    self.confirming = None;
+
                     match element.element_type {
                         ElementType::Application => {
                             crate::log!("Launching: {}", element.name);
@@ -176,55 +448,45 @@ impl AppState {
                         }
                         ElementType::SystemCommand => {
                             crate::log!("Executing system command: {}", element.name);
-                            let command = element.value.as_ref();
+                            let Some(command) = self.expand_placeholders(idx) else {
+                                return Ok(());
+                            };
 
                             if command.trim().starts_with("frisk ") || command.trim() == "frisk" {
                                 // Parse frisk arguments and reload current instance
-                                let args: Vec<&str> = command.split_whitespace().skip(1).collect();
-                                let mut apps = false;
-                                let mut homebrew = false;
-                                let mut clipboard = false;
-                                let mut commands = false;
-                                let mut nixpkgs = false;
-                                let mut dictionary = false;
-                                let sources = vec![];
-                                let mut prompt = None;
-                                let mut i = 0;
-
-                                while i < args.len() {
-                                    match args[i] {
-                                        "--apps" => apps = true,
-                                        "--homebrew" => homebrew = true,
-                                        "--clipboard" => clipboard = true,
-                                        "--commands" => commands = true,
-                                        "--nixpkgs" => nixpkgs = true,
-                                        "--dictionary" => dictionary = true,
-                                        "--prompt" | "-p" => {
-                                            if i + 1 < args.len() {
-                                                i += 1;
-                                                // Remove quotes if present
-                                                let p = args[i];
-                                                let p = p.trim_matches(|c| c == '"' || c == '\'');
-                                                prompt = Some(p.to_string());
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                    i += 1;
+                                let cli =
+                                    Cli::try_parse_from(split_words(&command)).map_err(|e| {
+                                        Error::new(format!("Invalid frisk command: {}", e))
+                                    })?;
+                                if cli.command.is_none() {
+                                    let paths = cli
+                                        .sources
+                                        .source
+                                        .iter()
+                                        .map(|p| p.display().to_string())
+                                        .collect();
+                                    return self.handle_reload(
+                                        cli.sources.names(),
+                                        paths,
+                                        cli.prompt,
+                                    );
                                 }
-
-                                self.handle_reload(
-                                    apps, homebrew, clipboard, commands, nixpkgs, dictionary, sources, prompt,
-                                );
-                                return Ok(());
                             }
 
-                            Command::new("sh")
-                                .arg("-c")
-                                .arg(command)
-                                .spawn()
-                                .map_err(|e| Error::new(format!("Failed to execute: {}", e)))?;
-                            self.should_exit = true;
+                            let options = self
+                                .elements
+                                .command_options(idx)
+                                .cloned()
+                                .unwrap_or_default();
+                            match exec::run(&command, &options, &self.config.terminal)? {
+                                Some(process) => {
+                                    let status = format!("Running {}...", element.name);
+                                    self.start_job(process, JobKind::Command(element), status);
+                                    // Recorded once it succeeds
+                                    return Ok(());
+                                }
+                                None => self.should_exit = true,
+                            }
                         }
                         ElementType::ClipboardHistory => {
                             crate::log!("Copying clipboard history entry: {}", element.value);
@@ -268,6 +530,47 @@ impl AppState {
                                 .map_err(|e| Error::new(format!("Failed to open URL: {}", e)))?;
                             self.should_exit = true;
                         }
+                        ElementType::Quicklink => {
+                            crate::log!("Opening quicklink: {}", element.value);
+                            Command::new("open")
+                                .arg(element.value.as_ref())
+                                .spawn()
+                                .map_err(|e| Error::new(format!("Failed to open URL: {}", e)))?;
+                            self.should_exit = true;
+                        }
+                        ElementType::ScriptFilter => {
+                            crate::log!("Running script filter: {}", element.name);
+                            let Some(command) = self.expand_placeholders(idx) else {
+                                return Ok(());
+                            };
+
+                            let options = self
+                                .elements
+                                .command_options(idx)
+                                .cloned()
+                                .unwrap_or_default();
+                            let status = format!("Running {}...", element.name);
+                            self.start_job(
+                                script::spawn(&command, &options)?,
+                                JobKind::ScriptFilter(element),
+                                status,
+                            );
+                            // Recorded once it succeeds
+                            return Ok(());
+                        }
+                        ElementType::Text => {
+                            crate::log!("Copying text: {}", element.value);
+                            let pasteboard = NSPasteboard::generalPasteboard();
+                            pasteboard.clearContents();
+                            let ns_string = NSString::from_str(&element.value);
+                            if unsafe {
+                                pasteboard.setString_forType(&ns_string, NSPasteboardTypeString)
+                            } {
+                                self.should_exit = true;
+                            } else {
+                                return Err(Error::new("Failed to copy"));
+                            }
+                        }
                         ElementType::Dictionary => {
                             crate::log!("Copying definition: {}", element.value);
                             let pasteboard = NSPasteboard::generalPasteboard();
@@ -282,12 +585,51 @@ impl AppState {
                             }
                         }
                     }
+
+                    record_launch(&element);
                 }
             }
         }
         Ok(())
     }
 
+    /// Expand the placeholders in an element's action, or return None after
+    /// switching to asking for its `{input}`
+    fn expand_placeholders(&mut self, idx: usize) -> Option<String> {
+        let element = &self.elements.inner[idx];
+        let action = element.value.to_string();
+        if !placeholder::is_parameterized(&action) {
+            return Some(action);
+        }
+
+        let argument = if self.pending_command == Some(idx) {
+            // Enter on an empty prompt keeps asking
+            Some(self.query.as_str()).filter(|query| !query.trim().is_empty())
+        } else {
+            placeholder::typed_argument(&element.name, &self.query[self.scope_prefix_len..])
+        };
+
+        if argument.is_none() && placeholder::needs_input(&action) {
+            // Ask for the argument, then run on the next Enter
+            self.pending_command = Some(idx);
+            self.query.clear();
+            self.cursor_position = 0;
+            self.update_search();
+            return None;
+        }
+
+        let clipboard = if action.contains("{clipboard}") {
+            pasteboard_string().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        Some(placeholder::expand_action(
+            &action,
+            argument.unwrap_or(""),
+            &clipboard,
+        ))
+    }
+
     pub fn delete_char(&mut self) {
         if self.cursor_position > 0 {
             let mut new_pos = self.cursor_position - 1;
@@ -337,9 +679,7 @@ impl AppState {
     }
 
     pub fn paste(&mut self) {
-        let pasteboard = NSPasteboard::generalPasteboard();
-        if let Some(text) = unsafe { pasteboard.stringForType(NSPasteboardTypeString) } {
-            let paste_str = text.to_string();
+        if let Some(paste_str) = pasteboard_string() {
             for c in paste_str.chars() {
                 if !c.is_control() || c == '\n' || c == '\t' {
                     // Convert newlines and tabs to spaces
@@ -383,13 +723,17 @@ impl AppState {
     }
 
     pub fn autocomplete(&mut self) {
-        if let Some(&idx) = self.filtered_indices.get(self.selected_index) {
-            if let Some(element) = self.elements.inner.get(idx) {
-                self.query.clear();
-                self.query.push_str(&element.name);
-                self.cursor_position = self.query.len();
-                self.update_search();
-            }
+        // The calculator result or quicklink above the list has no name to complete
+        if self.selected_index == 0 && self.top_result.is_some() {
+            return;
+        }
+
+        if let Some(name) = self.selected_element().map(|element| element.name.clone()) {
+            // Keep the scope prefix so autocompletion stays in the same source
+            self.query.truncate(self.scope_prefix_len);
+            self.query.push_str(&name);
+            self.cursor_position = self.query.len();
+            self.update_search();
         }
     }
 
@@ -399,100 +743,229 @@ impl AppState {
         }
     }
 
+    /// Back out of a confirmation or a pending command's argument input,
+    /// returning whether there was one
+    pub fn cancel_input(&mut self) -> bool {
+        if self.confirming.take().is_some() {
+            return true;
+        }
+        if self.pending_command.take().is_none() {
+            return false;
+        }
+        self.query.clear();
+        self.cursor_position = 0;
+        self.update_search();
+        true
+    }
+
+    pub fn cancel(&self) {
+        // dmenu exits non-zero when nothing was chosen so scripts can bail out
+        if self.mode == PickerMode::Dmenu {
+            std::process::exit(1);
+        }
+        self.terminate();
+    }
+
     pub fn handle_reload(
         &mut self,
-        apps: bool,
-        homebrew: bool,
-        clipboard: bool,
-        commands: bool,
-        nixpkgs: bool,
-        dictionary: bool,
         sources: Vec<String>,
+        paths: Vec<String>,
         prompt: Option<String>,
-    ) {
+    ) -> Result<()> {
         crate::log!(
-            "Reloading with: apps={}, homebrew={}, clipboard={}, commands={}, nixpkgs={}, dictionary={}, sources={:?}, prompt={:?}",
-            apps,
-            homebrew,
-            clipboard,
-            commands,
-            nixpkgs,
-            dictionary,
+            "Reloading with: sources={:?}, paths={:?}, prompt={:?}",
             sources,
+            paths,
             prompt
         );
 
         // Update prompt if provided
         if let Some(new_prompt) = prompt {
-            self.config.prompt = new_prompt;
+            self.config.override_prompt(new_prompt);
         }
 
         // Clear query on reload
         self.query.clear();
         self.cursor_position = 0;
+        self.pending_command = None;
+
+        let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+        // The old elements stay on failure, and the sender gets the error
+        let result = match crate::loader::load_elements(&self.config.sources, &sources, &paths) {
+            Ok(elements) => {
+                self.elements = elements;
+                self.listing = Listing::Sources;
+                Ok(())
+            }
+            Err(e) => {
+                crate::log!("Failed to reload elements: {}", e);
+                Err(e)
+            }
+        };
+
+        self.update_search();
+        crate::log!("Reloaded {} elements", self.elements.len());
+        result
+    }
 
-        let mut new_elements = crate::core::element::ElementList::new();
+    fn start_job(&mut self, process: Background, kind: JobKind, status: String) {
+        self.status = Some(status.clone());
+        self.job = Some(Job {
+            process,
+            kind,
+            status,
+        });
+    }
 
-        if apps {
-            if let Ok(Some(app_list)) = crate::loader::load_binary_source("apps.bin") {
-                for app in app_list {
-                    new_elements.add(app);
+    /// Finish the background job if it has exited, returning whether it did
+    pub fn poll_job(&mut self) -> bool {
+        let Some(result) = self.job.as_ref().and_then(|job| job.process.finished()) else {
+            return false;
+        };
+        let Some(job) = self.job.take() else {
+            return false;
+        };
+
+        match job.kind {
+            JobKind::ScriptFilter(element) => match script::finish(result) {
+                Ok(items) => {
+                    record_launch(&element);
+                    let mut elements = ElementList::new();
+                    elements.set_history(History::load());
+                    for item in items {
+                        elements.add(item);
+                    }
+                    crate::log!("Script filter returned {} items", elements.len());
+
+                    self.elements = elements;
+                    self.listing = Listing::ScriptFilter;
+                    self.pending_command = None;
+                    self.query.clear();
+                    self.cursor_position = 0;
+                    self.update_search();
                 }
-            }
+                Err(e) => {
+                    crate::log!("{}", e);
+                    self.status = Some(e.to_string());
+                }
+            },
+            JobKind::Command(element) => match exec::check(result) {
+                Ok(()) => {
+                    record_launch(&element);
+                    self.should_exit = true;
+                }
+                Err(e) => {
+                    crate::log!("Command failed: {}", e);
+                    self.status = Some(e.to_string());
+                }
+            },
         }
+        true
+    }
 
-        if homebrew {
-            if let Ok(Some(brew_list)) = crate::loader::load_binary_source("homebrew.bin") {
-                for item in brew_list {
-                    new_elements.add(item);
-                }
-            }
+    /// Add items sent over IPC. None are added if any of them is invalid, and
+    /// they're gone after the next reload.
+    pub fn push(&mut self, items: Vec<ScriptItem>) -> Result<()> {
+        let elements = script::pushed_elements(items)?;
+
+        crate::log!("Pushed {} elements", elements.len());
+        for element in elements {
+            self.elements.add(element);
         }
+        self.update_search();
+        Ok(())
+    }
 
-        if clipboard {
-            if let Ok(Some(clip_list)) = crate::loader::load_binary_source("clipboard.bin") {
-                for item in clip_list {
-                    new_elements.add(item);
-                }
+    fn watch_config(&mut self) {
+        self.watcher = match FileWatcher::new(self.config.files()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                crate::log!("Failed to watch config files: {}", e);
+                None
             }
+        };
+    }
+
+    /// Reload the config and commands if they changed on disk, returning
+    /// whether they did. Parse errors are shown in the picker and the previous
+    /// config or elements are kept.
+    pub fn reload_config(&mut self) -> bool {
+        let changed = match &self.watcher {
+            Some(watcher) => watcher.changed(),
+            None => return false,
+        };
+        if changed.is_empty() {
+            return false;
         }
+        crate::log!("Config changed: {:?}", changed);
 
-        if nixpkgs {
-            if let Ok(Some(nix_list)) = crate::loader::load_binary_source("nixpkgs.bin") {
-                for item in nix_list {
-                    new_elements.add(item);
-                }
+        let mut config = match Config::load(Some(self.config.path.clone())) {
+            Ok(config) => config,
+            Err(e) => {
+                self.reload_error(format!("{}: {}", self.config.path.display(), e));
+                return true;
             }
+        };
+        if let Some(prompt) = &self.config.prompt_override {
+            config.override_prompt(prompt.clone());
         }
 
-        if dictionary {
-            if let Ok(Some(dict_list)) = crate::loader::load_binary_source("dictionary.bin") {
-                for item in dict_list {
-                    new_elements.add(item);
-                }
+        // The loader would skip a broken commands file with a warning, so
+        // report it and keep the elements until it is fixed
+        let config_file = fs::canonicalize(&config.path).unwrap_or_else(|_| config.path.clone());
+        let commands_files: Vec<PathBuf> = changed
+            .into_iter()
+            .filter(|path| *path != config_file)
+            .collect();
+        let mut error = CommandsConfig::check_files(&commands_files)
+            .err()
+            .map(|e| e.to_string());
+
+        if self.listing == Listing::Sources && error.is_none() {
+            match crate::loader::load_elements(
+                &config.sources,
+                &self.elements.loaded_sources,
+                &self.elements.loaded_paths,
+            ) {
+                Ok(elements) => self.elements = elements,
+                Err(e) => error = Some(format!("Failed to reload elements: {}", e)),
             }
         }
 
-        for source_path in sources {
-            if let Ok(items) =
-                crate::loader::load_binary_file(&std::path::PathBuf::from(source_path))
-            {
-                for item in items {
-                    new_elements.add(item);
-                }
-            }
+        self.config = config;
+        self.dynamic_max_results = Self::calculate_max_results(
+            self.window_height,
+            self.config.font_size,
+            self.menubar_height,
+        );
+        self.pending_command = None;
+        self.watch_config();
+
+        self.update_search();
+        if let Some(error) = error {
+            self.reload_error(error);
         }
+        crate::log!("Reloaded config, {} elements", self.elements.len());
+        true
+    }
 
-        if commands {
-            if let Ok(commands_config) = crate::core::commands::CommandsConfig::load() {
-                for cmd in commands_config.to_elements() {
-                    new_elements.add(cmd);
-                }
-            }
+    /// Show a reload error, unless a running job's status is showing
+    fn reload_error(&mut self, error: String) {
+        if self.job.is_some() {
+            crate::log!("Config reload failed: {}", error);
+        } else {
+            self.status = Some(error);
         }
+    }
+}
 
-        self.elements = new_elements;
-        self.update_search();
-        crate::log!("Reloaded {} elements", self.elements.len());
+fn pasteboard_string() -> Option<String> {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    unsafe { pasteboard.stringForType(NSPasteboardTypeString) }.map(|text| text.to_string())
+}
+
+fn record_launch(element: &Element) {
+    if let Err(e) = crate::core::history::record(element) {
+        crate::log!("Failed to record launch history: {}", e);
     }
 }
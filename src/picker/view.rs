@@ -1,27 +1,25 @@
-use super::rendering::{draw_cursor, draw_text, measure_text_width};
-use super::state::AppState;
+use super::rendering::{draw_cursor, draw_highlighted_text, draw_text, measure_text_width};
+use super::state::{AppState, PickerMode};
 use crate::core::config::Config;
 use crate::core::element::ElementList;
-use crate::ipc::IpcMessage;
+use crate::core::keymap::{Action, Chord, Key};
+use crate::ipc::server::IpcRequest;
+use crate::ipc::{IpcMessage, Reply};
+use crate::query;
 use objc2::rc::Retained;
-use objc2::{define_class, msg_send, DefinedClass, MainThreadOnly};
-use objc2_app_kit::{NSBezierPath, NSEvent, NSView};
-use objc2_foundation::NSRect;
+use objc2::{define_class, msg_send, sel, DefinedClass, MainThreadOnly};
+use objc2_app_kit::{NSBezierPath, NSEvent, NSEventModifierFlags, NSView};
+use objc2_foundation::{NSRect, NSTimeInterval, NSTimer};
 use std::cell::RefCell;
 use std::sync::mpsc::Receiver;
 
-const KEY_ESCAPE: u16 = 53;
-const KEY_ENTER: u16 = 36;
-const KEY_TAB: u16 = 48;
-const KEY_BACKSPACE: u16 = 51;
-const KEY_LEFT: u16 = 123;
-const KEY_RIGHT: u16 = 124;
-const KEY_DOWN: u16 = 125;
-const KEY_UP: u16 = 126;
+/// How often the main thread looks for IPC requests, finished jobs and
+/// config changes
+const POLL_INTERVAL: NSTimeInterval = 0.05;
 
 pub struct Ivars {
     state: RefCell<AppState>,
-    ipc_rx: RefCell<Option<Receiver<IpcMessage>>>,
+    ipc_rx: RefCell<Option<Receiver<IpcRequest>>>,
 }
 
 define_class!(
@@ -33,28 +31,31 @@ define_class!(
     impl CustomView {
         #[unsafe(method(drawRect:))]
         fn draw_rect(&self, _dirty_rect: NSRect) {
-            if let Some(rx) = self.ivars().ipc_rx.borrow().as_ref() {
-                if let Ok(msg) = rx.try_recv() {
-                    self.handle_ipc_message(msg);
-                }
-            }
-
             let mut state = self.ivars().state.borrow_mut();
 
             state.update_string_caches();
+            state.update_match_positions();
 
             let bounds = self.bounds();
 
             state.config.background_color.setFill();
             NSBezierPath::fillRect(bounds);
 
-            let padding = state.config.window_padding as f64;
-            let prompt_y = bounds.size.height - padding - state.menubar_height;
+            let padding = state.config.window_padding_x;
+            let prompt_y = bounds.size.height - state.config.window_padding_y - state.menubar_height;
 
+            let prompt = &state.config.prompt;
             draw_text(
-                &state.prompt_query_cache,
+                prompt,
                 padding,
                 prompt_y,
+                &state.config.prompt_color,
+                &state.config.font,
+            );
+            draw_text(
+                &state.prompt_query_cache[prompt.len()..],
+                padding + measure_text_width(prompt, &state.config.font),
+                prompt_y,
                 &state.config.query_color,
                 &state.config.font,
             );
@@ -65,15 +66,15 @@ define_class!(
                 cursor_x,
                 prompt_y,
                 &state.config.caret_color,
-                state.config.font_size as f64
+                state.config.font_size
             );
 
-            let line_height = state.config.font_size as f64 + state.config.item_spacing as f64;
-            let results_start_y = prompt_y - state.config.prompt_to_items as f64;
+            let line_height = state.config.font_size + state.config.item_spacing;
+            let results_start_y = prompt_y - state.config.prompt_to_items;
 
             let mut display_idx = 0;
 
-            if let Some(calc_result) = &state.calculator_result {
+            if let Some(calc_result) = &state.top_result {
                 if display_idx >= state.scroll_offset && display_idx < state.scroll_offset + state.dynamic_max_results {
                     let y = results_start_y - ((display_idx - state.scroll_offset) as f64 * line_height);
                     let text_color = if display_idx == state.selected_index {
@@ -93,7 +94,7 @@ define_class!(
                 display_idx += 1;
             }
 
-            for &elem_idx in &state.filtered_indices {
+            for (match_idx, &elem_idx) in state.filtered_indices.iter().enumerate() {
                 if display_idx >= state.scroll_offset && display_idx < state.scroll_offset + state.dynamic_max_results {
                     if let Some(element) = state.elements.inner.get(elem_idx) {
                         let y = results_start_y - ((display_idx - state.scroll_offset) as f64 * line_height);
@@ -103,13 +104,25 @@ define_class!(
                             &state.config.items_color
                         };
 
-                        draw_text(
-                            &element.name,
-                            padding,
-                            y,
-                            text_color,
-                            &state.config.font,
-                        );
+                        if state.confirming == Some(elem_idx) {
+                            draw_text(
+                                &format!("{}? {}", element.name, state.config.keymap.confirm_hint()),
+                                padding,
+                                y,
+                                text_color,
+                                &state.config.font,
+                            );
+                        } else {
+                            draw_highlighted_text(
+                                &element.name,
+                                padding,
+                                y,
+                                text_color,
+                                &state.config.font,
+                                state.match_positions.get(&match_idx).map(Vec::as_slice).unwrap_or_default(),
+                                &state.config.match_color,
+                            );
+                        }
                     }
                 }
                 display_idx += 1;
@@ -118,7 +131,17 @@ define_class!(
                 }
             }
 
-            let has_results = state.calculator_result.is_some() || !state.filtered_indices.is_empty();
+            if let Some(status) = &state.status {
+                draw_text(
+                    status,
+                    padding,
+                    state.config.window_padding_y,
+                    &state.config.query_color,
+                    &state.config.font,
+                );
+            }
+
+            let has_results = state.top_result.is_some() || !state.filtered_indices.is_empty();
             if !has_results && !state.query.is_empty() {
                 draw_text(
                     "No results",
@@ -130,6 +153,30 @@ define_class!(
             }
         }
 
+        #[unsafe(method(poll:))]
+        fn poll(&self, _timer: &NSTimer) {
+            let mut state = self.ivars().state.borrow_mut();
+            let mut handled = state.poll_job();
+            if state.should_exit {
+                drop(state);
+                self.ivars().state.borrow().terminate();
+                return;
+            }
+            handled |= state.reload_config();
+            drop(state);
+
+            if let Some(rx) = self.ivars().ipc_rx.borrow().as_ref() {
+                while let Ok(request) = rx.try_recv() {
+                    let reply = self.handle_ipc_message(request.message.clone());
+                    request.reply(reply);
+                    handled = true;
+                }
+            }
+            if handled {
+                self.setNeedsDisplay(true);
+            }
+        }
+
         #[unsafe(method(acceptsFirstResponder))]
         fn accepts_first_responder(&self) -> bool {
             true
@@ -143,123 +190,21 @@ define_class!(
 
         #[unsafe(method(keyDown:))]
         fn key_down(&self, event: &NSEvent) {
-            let key_code = event.keyCode();
-            let modifiers = event.modifierFlags();
-
-            let ctrl = modifiers.contains(objc2_app_kit::NSEventModifierFlags::Control);
-            let cmd = modifiers.contains(objc2_app_kit::NSEventModifierFlags::Command);
-
-            crate::log!("Key: code={}, ctrl={}, cmd={}", key_code, ctrl, cmd);
-
-            if ctrl && !cmd {
-                if let Some(characters) = event.charactersIgnoringModifiers() {
-                    let text = characters.to_string();
-                    crate::log!("Ctrl+key: {:?}", text);
-                    match text.to_lowercase().as_str() {
-                        "w" => {
-                            self.ivars().state.borrow_mut().delete_word();
-                            self.setNeedsDisplay(true);
-                            return;
-                        }
-                        "u" => {
-                            self.ivars().state.borrow_mut().delete_to_start();
-                            self.setNeedsDisplay(true);
-                            return;
-                        }
-                        "n" => {
-                            self.ivars().state.borrow_mut().nav_down();
-                            self.setNeedsDisplay(true);
-                            return;
-                        }
-                        "p" => {
-                            self.ivars().state.borrow_mut().nav_up();
-                            self.setNeedsDisplay(true);
-                            return;
-                        }
-                        "y" => {
-                            let mut state = self.ivars().state.borrow_mut();
-                            if let Err(e) = state.execute_selected() {
-                                eprintln!("[kickoff] Failed to execute: {}", e);
-                            }
-                            if state.should_exit {
-                                drop(state);
-                                self.ivars().state.borrow().terminate();
-                            }
-                            return;
-                        }
-                        _ => {}
-                    }
-                }
-            }
+            let Some(chord) = Self::chord(event) else {
+                return;
+            };
+            crate::log!("Key: {} (code={})", chord, event.keyCode());
 
-            if cmd && !ctrl {
-                if let Some(characters) = event.charactersIgnoringModifiers() {
-                    if characters.to_string().to_lowercase() == "v" {
-                        self.ivars().state.borrow_mut().paste();
-                        self.setNeedsDisplay(true);
-                        return;
-                    }
-                }
+            let action = self.ivars().state.borrow().config.keymap.action(&chord);
+            if let Some(action) = action {
+                crate::log!("Action: {}", action.name());
+                self.run_action(action);
+                return;
             }
 
-            match key_code {
-                KEY_ESCAPE => {
-                    self.ivars().state.borrow().terminate();
-                    return;
-                }
-                KEY_ENTER => {
-                    let mut state = self.ivars().state.borrow_mut();
-                    if let Err(e) = state.execute_selected() {
-                        eprintln!("[kickoff] Failed to execute: {}", e);
-                    }
-                    if state.should_exit {
-                        drop(state);
-                        self.ivars().state.borrow().terminate();
-                    }
-                    self.setNeedsDisplay(true);
-                    return;
-                }
-                KEY_UP => {
-                    if !ctrl {
-                        self.ivars().state.borrow_mut().nav_up();
-                        self.setNeedsDisplay(true);
-                    }
-                    return;
-                }
-                KEY_DOWN => {
-                    if !ctrl {
-                        self.ivars().state.borrow_mut().nav_down();
-                        self.setNeedsDisplay(true);
-                    }
-                    return;
-                }
-                KEY_TAB => {
-                    self.ivars().state.borrow_mut().autocomplete();
-                    self.setNeedsDisplay(true);
-                    return;
-                }
-                KEY_BACKSPACE => {
-                    let mut state = self.ivars().state.borrow_mut();
-                    if ctrl {
-                        state.delete_word();
-                    } else {
-                        state.delete_char();
-                    }
-                    drop(state);
-                    self.setNeedsDisplay(true);
-                    return;
-                }
-                KEY_LEFT => {
-                    self.ivars().state.borrow_mut().move_cursor_left();
-                    self.setNeedsDisplay(true);
-                    return;
-                }
-                KEY_RIGHT => {
-                    self.ivars().state.borrow_mut().move_cursor_right();
-                    self.setNeedsDisplay(true);
-                    return;
-                }
-                _ => {}
+            // Unbound named keys and shortcuts don't type anything
+            if !matches!(chord.key, Key::Char(_)) || chord.ctrl || chord.cmd {
+                return;
             }
 
             if let Some(characters) = event.characters() {
@@ -283,10 +228,11 @@ impl CustomView {
         elements: ElementList,
         window_height: f64,
         menubar_height: f64,
-        ipc_rx: Option<Receiver<IpcMessage>>,
+        ipc_rx: Option<Receiver<IpcRequest>>,
+        mode: PickerMode,
         mtm: objc2::MainThreadMarker,
     ) -> Retained<Self> {
-        unsafe {
+        let view: Retained<Self> = unsafe {
             msg_send![
                 super(Self::alloc(mtm).set_ivars(Ivars {
                     state: RefCell::new(AppState::new(
@@ -294,32 +240,131 @@ impl CustomView {
                         elements,
                         window_height,
                         menubar_height,
+                        mode,
                     )),
                     ipc_rx: RefCell::new(ipc_rx),
                 })),
                 init
             ]
+        };
+
+        // The picker only redraws on input, so IPC requests, background jobs
+        // and config changes are polled on a timer
+        unsafe {
+            NSTimer::scheduledTimerWithTimeInterval_target_selector_userInfo_repeats(
+                POLL_INTERVAL,
+                &view,
+                sel!(poll:),
+                None,
+                true,
+            );
+        }
+
+        view
+    }
+
+    /// The key pressed with its modifiers, named keys by key code and the
+    /// rest by the character they type without modifiers
+    fn chord(event: &NSEvent) -> Option<Chord> {
+        let modifiers = event.modifierFlags();
+        let key = match Key::from_key_code(event.keyCode()) {
+            Some(key) => key,
+            None => {
+                let characters = event.charactersIgnoringModifiers()?.to_string();
+                Key::Char(characters.to_lowercase().chars().next()?)
+            }
+        };
+
+        Some(Chord {
+            ctrl: modifiers.contains(NSEventModifierFlags::Control),
+            alt: modifiers.contains(NSEventModifierFlags::Option),
+            shift: modifiers.contains(NSEventModifierFlags::Shift),
+            cmd: modifiers.contains(NSEventModifierFlags::Command),
+            key,
+        })
+    }
+
+    fn run_action(&self, action: Action) {
+        let mut state = self.ivars().state.borrow_mut();
+
+        match action {
+            Action::NavUp => state.nav_up(),
+            Action::NavDown => state.nav_down(),
+            Action::MoveLeft => state.move_cursor_left(),
+            Action::MoveRight => state.move_cursor_right(),
+            Action::DeleteChar => state.delete_char(),
+            Action::DeleteWord => state.delete_word(),
+            Action::DeleteToStart => state.delete_to_start(),
+            Action::Autocomplete => state.autocomplete(),
+            Action::Paste => state.paste(),
+            Action::Execute | Action::ExecuteAlt => {
+                let result = match action {
+                    Action::Execute => state.execute_selected(),
+                    _ => state.execute_query(),
+                };
+                if let Err(e) = result {
+                    eprintln!("[kickoff] Failed to execute: {}", e);
+                    state.status = Some(e.to_string());
+                }
+                if state.should_exit {
+                    drop(state);
+                    self.ivars().state.borrow().terminate();
+                    return;
+                }
+            }
+            Action::Cancel => {
+                if !state.cancel_input() {
+                    drop(state);
+                    self.ivars().state.borrow().cancel();
+                    return;
+                }
+            }
+            Action::Quit => {
+                drop(state);
+                self.ivars().state.borrow().cancel();
+                return;
+            }
         }
+
+        drop(state);
+        self.setNeedsDisplay(true);
     }
 
-    fn handle_ipc_message(&self, msg: IpcMessage) {
+    fn handle_ipc_message(&self, msg: IpcMessage) -> Reply {
         match msg {
             IpcMessage::Reload {
-                apps,
-                homebrew,
-                clipboard,
-                commands,
-                nixpkgs,
-                dictionary,
                 sources,
+                paths,
                 prompt,
             } => {
                 let mut state = self.ivars().state.borrow_mut();
-                state.handle_reload(
-                    apps, homebrew, clipboard, commands, nixpkgs, dictionary, sources, prompt,
-                );
+                match state.handle_reload(sources, paths, prompt) {
+                    Ok(()) => Reply::Ok,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            IpcMessage::Search {
+                query,
+                source,
+                limit,
+            } => {
+                let mut state = self.ivars().state.borrow_mut();
+                let scope = source.scope();
+                Reply::Results(query::rank(
+                    &mut state.elements,
+                    &query,
+                    scope.as_ref(),
+                    limit,
+                ))
+            }
+            IpcMessage::Ping => Reply::Pong,
+            IpcMessage::Push { items } => {
+                let mut state = self.ivars().state.borrow_mut();
+                match state.push(items) {
+                    Ok(()) => Reply::Ok,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
             }
-            IpcMessage::Search { .. } => {}
         }
     }
 }
@@ -1,8 +1,9 @@
+use super::state::PickerMode;
 use super::view::CustomView;
 use crate::core::config::Config;
 use crate::core::element::ElementList;
 use crate::core::error::{Error, Result};
-use crate::ipc::IpcMessage;
+use crate::ipc::server::IpcRequest;
 use objc2::rc::Retained;
 use objc2::{define_class, msg_send, MainThreadMarker, MainThreadOnly};
 use objc2_app_kit::{
@@ -16,7 +17,8 @@ pub fn create_window(
     mtm: MainThreadMarker,
     config: Config,
     elements: ElementList,
-    ipc_rx: Option<Receiver<IpcMessage>>,
+    ipc_rx: Option<Receiver<IpcRequest>>,
+    mode: PickerMode,
 ) -> Result<Retained<BorderlessKeyWindow>> {
     let active_screen =
         NSScreen::mainScreen(mtm).ok_or_else(|| Error::new("Failed to find main screen"))?;
@@ -42,6 +44,7 @@ pub fn create_window(
         window_rect.size.height,
         active_screen.frame().size.height - active_screen.visibleFrame().size.height,
         ipc_rx,
+        mode,
         mtm,
     );
 
@@ -0,0 +1,39 @@
+use std::io::{self, Read};
+
+use crate::core::error::{Error, Result};
+use crate::core::script::{self, ScriptItem};
+use crate::ipc::{self, IpcMessage};
+
+/// Send one item to the running picker, or JSON lines of items from stdin
+/// when there's no name
+pub fn run(
+    name: Option<String>,
+    value: Option<String>,
+    element_type: Option<String>,
+    action: Option<String>,
+) -> Result<()> {
+    let items = match name {
+        Some(name) => vec![ScriptItem {
+            name,
+            value,
+            element_type,
+            action,
+            confirm: false,
+        }],
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            script::parse_items(&input)?
+        }
+    };
+
+    if items.is_empty() {
+        return Err(Error::new("Nothing to push"));
+    }
+    // Checked here too so a bad item is reported without asking the picker
+    script::pushed_elements(items.clone())?;
+
+    crate::log!("Pushing {} items", items.len());
+    ipc::send_message(&IpcMessage::Push { items })?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::SourceArgs;
+use crate::core::calculator::Calculator;
+use crate::core::element::{ElementList, ElementType, Scope};
+use crate::core::error::Result;
+use crate::core::source::SourceRegistry;
+use crate::loader::load_elements;
+
+/// A ranked result, as printed by `frisk query` and sent over IPC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub name: String,
+    pub value: String,
+    #[serde(rename = "type")]
+    pub element_type: String,
+    /// Calculator results have no score
+    pub score: Option<u32>,
+}
+
+pub fn run(
+    query: &str,
+    sources: &SourceArgs,
+    json: bool,
+    limit: Option<usize>,
+    config_path: Option<PathBuf>,
+) -> Result<()> {
+    let registry = SourceRegistry::load(config_path)?;
+    let mut elements = load_elements(&registry, &sources.names(), &sources.source)?;
+    let results = results(&mut elements, query, limit);
+    print(&results, json, &mut io::stdout().lock())
+}
+
+/// The calculator's answer, if any, then the ranked elements
+fn results(elements: &mut ElementList, query: &str, limit: Option<usize>) -> Vec<QueryResult> {
+    // Like the picker, carry on without the calculator if it can't start
+    let calculator_result = Calculator::new()
+        .ok()
+        .and_then(|mut calculator| calculator.evaluate(query));
+
+    let mut results = Vec::new();
+
+    if let Some(result) = calculator_result {
+        results.push(QueryResult {
+            name: result.clone(),
+            value: result,
+            element_type: ElementType::CalculatorResult.as_str().to_string(),
+            score: None,
+        });
+    }
+
+    results.extend(rank(elements, query, None, limit));
+
+    if let Some(limit) = limit {
+        results.truncate(limit);
+    }
+
+    results
+}
+
+/// Search `elements`, or only those in `scope`, best match first
+pub fn rank(
+    elements: &mut ElementList,
+    query: &str,
+    scope: Option<&Scope>,
+    limit: Option<usize>,
+) -> Vec<QueryResult> {
+    let matches = elements.search_in(query, scope);
+
+    matches
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|m| {
+            let element = &elements.inner[m.index];
+            QueryResult {
+                name: element.name.to_string(),
+                value: element.value.to_string(),
+                element_type: element.element_type.as_str().to_string(),
+                score: Some(m.score),
+            }
+        })
+        .collect()
+}
+
+/// Results as a JSON array, or a line of score, type and name each
+pub fn print(results: &[QueryResult], json: bool, out: &mut impl Write) -> Result<()> {
+    if json {
+        serde_json::to_writer_pretty(&mut *out, results)?;
+        writeln!(out)?;
+    } else {
+        for result in results {
+            let score = result
+                .score
+                .map_or_else(|| "-".to_string(), |s| s.to_string());
+            writeln!(out, "{}\t{}\t{}", score, result.element_type, result.name)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::element::Element;
+
+    fn elements() -> ElementList {
+        let mut elements = ElementList::new();
+        for name in ["Firefox", "Finder", "Fire Alarm", "Calendar"] {
+            elements.add(Element::new(
+                name.to_string(),
+                format!("/Applications/{}.app", name),
+            ));
+        }
+        elements
+    }
+
+    fn output(results: &[QueryResult], json: bool) -> String {
+        let mut out = Vec::new();
+        print(results, json, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_has_score_type_and_name_per_line() {
+        let results = results(&mut elements(), "fire", None);
+        let text = output(&results, false);
+        let lines: Vec<Vec<&str>> = text
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        for (line, result) in lines.iter().zip(&results) {
+            assert_eq!(line[0], result.score.unwrap().to_string());
+            assert_eq!(line[1], "application");
+            assert_eq!(line[2], result.name);
+        }
+        assert!(lines.iter().any(|line| line[2] == "Firefox"));
+    }
+
+    #[test]
+    fn json_lists_name_value_type_and_score() {
+        let results = results(&mut elements(), "finder", None);
+        let json: serde_json::Value = serde_json::from_str(&output(&results, true)).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "name": "Finder",
+                "value": "/Applications/Finder.app",
+                "type": "application",
+                "score": results[0].score,
+            }])
+        );
+    }
+
+    #[test]
+    fn calculator_result_comes_first_without_a_score() {
+        let results = results(&mut elements(), "2 * 21", None);
+
+        assert_eq!(results[0].name, "42");
+        assert_eq!(results[0].element_type, "calculator");
+        assert_eq!(results[0].score, None);
+        assert!(output(&results, false).starts_with("-\tcalculator\t42\n"));
+    }
+
+    #[test]
+    fn limit_counts_every_result() {
+        assert_eq!(results(&mut elements(), "fi", None).len(), 3);
+        assert_eq!(results(&mut elements(), "fi", Some(2)).len(), 2);
+        assert_eq!(results(&mut elements(), "2 * 21", Some(1)).len(), 1);
+    }
+}
@@ -42,8 +42,7 @@ fn discover_applications() -> Result<Vec<Element>> {
 
 fn save_apps(elements: &[Element]) -> Result<()> {
     let cache_path = crate::cache::cache_dir()?.join("apps.bin");
-    let vec = elements.to_vec();
-    crate::cache::save_cache("apps.bin", &vec)?;
+    crate::cache::save_cache("apps.bin", elements)?;
     eprintln!(
         "[apps daemon] Saved {} apps to {:?}",
         elements.len(),
@@ -51,9 +51,11 @@ pub fn run() -> Result<()> {
 }
 
 fn append_clipboard_entry(content: &str) -> Result<()> {
+    let lock = crate::cache::lock_cache("clipboard.bin")?;
+
     // Load existing history
     let mut elements: Vec<Element> =
-        crate::loader::load_binary_source("clipboard.bin")?.unwrap_or_default();
+        crate::loader::load_cache_locked("clipboard.bin", &lock)?.unwrap_or_default();
 
     // Create new element
     let normalized: String = content
@@ -154,8 +154,7 @@ fn fetch_dictionary() -> Result<Vec<Element>> {
 
 fn save_dictionary(elements: &[Element]) -> Result<()> {
     let cache_path = crate::cache::cache_dir()?.join("dictionary.bin");
-    let vec = elements.to_vec();
-    crate::cache::save_cache("dictionary.bin", &vec)?;
+    crate::cache::save_cache("dictionary.bin", elements)?;
     eprintln!(
         "[dictionary daemon] Saved {} entries to {:?}",
         elements.len(),
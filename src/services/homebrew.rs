@@ -84,8 +84,7 @@ fn fetch_homebrew() -> Result<Vec<Element>> {
 
 fn save_homebrew(elements: &[Element]) -> Result<()> {
     let cache_path = crate::cache::cache_dir()?.join("homebrew.bin");
-    let vec = elements.to_vec();
-    crate::cache::save_cache("homebrew.bin", &vec)?;
+    crate::cache::save_cache("homebrew.bin", elements)?;
     eprintln!(
         "[homebrew daemon] Saved {} packages to {:?}",
         elements.len(),
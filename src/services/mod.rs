@@ -6,6 +6,7 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::process::Command;
 pub mod apps;
+#[cfg(target_os = "macos")]
 pub mod clipboard;
 pub mod dictionary;
 pub mod homebrew;
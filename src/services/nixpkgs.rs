@@ -165,8 +165,7 @@ fn fetch_nixpkgs() -> Result<Vec<Element>> {
 
 fn save_nixpkgs(elements: &[Element]) -> Result<()> {
     let cache_path = crate::cache::cache_dir()?.join("nixpkgs.bin");
-    let vec = elements.to_vec();
-    crate::cache::save_cache("nixpkgs.bin", &vec)?;
+    crate::cache::save_cache("nixpkgs.bin", elements)?;
     eprintln!(
         "[nixpkgs daemon] Saved {} packages to {:?}",
         elements.len(),